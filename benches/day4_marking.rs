@@ -0,0 +1,108 @@
+// Compares bitmask boards against the Vec-of-cells scan they replaced, marking every
+// number on 100k boards. Run with `cargo bench --bench day4_marking`.
+#![allow(dead_code)]
+// The modules' own tests don't run in a bench, which leaves their test imports unused.
+#![cfg_attr(test, allow(unused_imports))]
+
+#[path = "../src/util.rs"]
+mod util;
+#[path = "../src/day4.rs"]
+mod day4;
+#[path = "../src/day4stats.rs"]
+mod day4stats;
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use itertools::Itertools;
+use day4::{Board, build_cell_indexes, Cell, play_number_on_all_boards, ROWS_AND_COLUMNS};
+use util::Rng;
+
+const NUM_RUNS: usize = 5;
+
+fn benchmark_marking(num_boards: usize, seed: u64) -> BenchmarkResult {
+    let mut rng = Rng::new(seed);
+    let board_numbers = (0..num_boards)
+        .map(|_| random_board_numbers(&mut rng))
+        .collect_vec();
+    let mut draws = (0..100).collect_vec();
+    rng.shuffle(&mut draws);
+
+    let boards = board_numbers.iter()
+        .map(|numbers| Board::from_cells(5, 5, numbers.iter().map(|&num| Cell::new(num, false)).collect_vec()))
+        .collect_vec();
+    let cell_indexes = build_cell_indexes(&boards);
+
+    let mut result = BenchmarkResult { num_winners: 0, scan_time: Duration::MAX, bitmask_time: Duration::MAX };
+    for _run in 0..NUM_RUNS {
+        let mut bitmask_boards = boards.clone();
+        let start = Instant::now();
+        let bitmask_win_order = draws.iter()
+            .flat_map(|&number| play_number_on_all_boards(number, &mut bitmask_boards, &cell_indexes, ROWS_AND_COLUMNS))
+            .collect_vec();
+        result.bitmask_time = result.bitmask_time.min(start.elapsed());
+
+        let mut scan_boards = (0..num_boards)
+            .map(|_board_index| ScanBoard { marked: vec![false; 25], bingo: false })
+            .collect_vec();
+        let start = Instant::now();
+        let scan_win_order = draws.iter()
+            .flat_map(|&number| play_number_on_all_scan_boards(number, &mut scan_boards, &cell_indexes))
+            .collect_vec();
+        result.scan_time = result.scan_time.min(start.elapsed());
+
+        assert_eq!(scan_win_order, bitmask_win_order);
+        result.num_winners = bitmask_win_order.len();
+    }
+    result
+}
+
+#[derive(Debug)]
+struct BenchmarkResult {
+    num_winners: usize,
+    scan_time: Duration,
+    bitmask_time: Duration,
+}
+
+fn random_board_numbers(rng: &mut Rng) -> Vec<u32> {
+    let mut pool = (0..100).collect_vec();
+    rng.shuffle(&mut pool);
+    pool.truncate(25);
+    pool
+}
+
+// The Vec-of-cells board that Board replaced, kept as the benchmark baseline.
+struct ScanBoard {
+    marked: Vec<bool>,
+    bingo: bool,
+}
+
+impl ScanBoard {
+    fn play_cell(&mut self, cell_index: usize) -> bool {
+        if self.bingo {
+            return false;
+        }
+        self.marked[cell_index] = true;
+        let row_first_index = (cell_index / 5) * 5;
+        let col_first_index = cell_index % 5;
+        self.bingo = (row_first_index..row_first_index + 5).all(|index| self.marked[index])
+            || (0..5).map(|i| col_first_index + i * 5).all(|index| self.marked[index]);
+        self.bingo
+    }
+}
+
+fn play_number_on_all_scan_boards(number: u32, boards: &mut [ScanBoard], cell_indexes: &HashMap<u32, Vec<(usize, usize)>>) -> Vec<usize> {
+    let mut winning_board_indexes = vec![];
+    if let Some(indexes) = cell_indexes.get(&number) {
+        for &(board_index, cell_index) in indexes {
+            if boards[board_index].play_cell(cell_index) {
+                winning_board_indexes.push(board_index);
+            }
+        }
+    }
+    winning_board_indexes
+}
+
+fn main() {
+    let result = benchmark_marking(100_000, 1);
+    println!("{} winners; scan: {:?}, bitmask: {:?}", result.num_winners, result.scan_time, result.bitmask_time);
+}
@@ -1,4 +1,4 @@
-use itertools::Itertools;
+use crate::util::match_pattern;
 
 #[allow(dead_code)]
 pub fn day2a(input: impl Iterator<Item=String>) -> (&'static str, i32) {
@@ -9,24 +9,26 @@ pub fn day2a(input: impl Iterator<Item=String>) -> (&'static str, i32) {
 
 fn move_submarine(input: impl Iterator<Item=String>) -> SubState {
     input
-        .map(|line| command_to_delta(&line))
+        .enumerate()
+        .map(|(line_index, line)| command_to_delta(&line, line_index + 1))
         .fold(SubState::new(0, 0), |state, delta| SubState {
             hpos: state.hpos + delta.hpos,
             depth: state.depth + delta.depth,
         })
 }
 
-fn command_to_delta(command: &str) -> SubState {
-    let split = command.split_whitespace().collect_vec();
-    let arg: u32 = String::from(split[1]).parse().unwrap();
-    if split[0] == "down" {
+fn command_to_delta(command: &str, line_number: usize) -> SubState {
+    let captures = match_pattern(command, line_number, "{} {}").unwrap_or_else(|error| panic!("{}", error));
+    let keyword = captures.str(0);
+    let arg: u32 = captures.parse(1).unwrap_or_else(|error| panic!("{}", error));
+    if keyword == "down" {
         SubState::new(0, arg as i32)
-    } else if split[0] == "forward" {
+    } else if keyword == "forward" {
         SubState::new(arg as i32, 0)
-    } else if split[0] == "up" {
+    } else if keyword == "up" {
         SubState::new(0, -(arg as i32))
     } else {
-        panic!("Unknown command {}", split[0]);
+        panic!("Unknown command {}", keyword);
     }
 }
 
@@ -52,17 +54,25 @@ mod tests {
 
     #[test]
     fn down_command_to_delta() {
-        assert_eq!(command_to_delta("down 4"), SubState::new(0, 4));
+        assert_eq!(command_to_delta("down 4", 1), SubState::new(0, 4));
     }
 
     #[test]
     fn forward_command_to_delta() {
-        assert_eq!(command_to_delta("forward 6"), SubState::new(6, 0));
+        assert_eq!(command_to_delta("forward 6", 1), SubState::new(6, 0));
     }
 
     #[test]
     fn up_command_to_delta() {
-        assert_eq!(command_to_delta("up 5"), SubState::new(0, -5));
+        assert_eq!(command_to_delta("up 5", 1), SubState::new(0, -5));
+    }
+
+    #[test]
+    fn command_to_delta_with_extra_whitespace() {
+        assert_eq!(command_to_delta("forward  5", 1), SubState::new(5, 0));
+        assert_eq!(command_to_delta("forward 5 ", 1), SubState::new(5, 0));
+        assert_eq!(command_to_delta("down 3\r", 1), SubState::new(0, 3));
+        assert_eq!(command_to_delta(" up\t2", 1), SubState::new(0, -2));
     }
 
     #[test]
@@ -1,4 +1,4 @@
-use itertools::Itertools;
+use crate::util::match_pattern;
 
 #[allow(dead_code)]
 pub fn day2b(input: impl Iterator<Item=String>) -> (&'static str, i32) {
@@ -9,7 +9,8 @@ pub fn day2b(input: impl Iterator<Item=String>) -> (&'static str, i32) {
 
 fn move_submarine(input: impl Iterator<Item=String>) -> SubState {
     input
-        .map(|command| command_to_delta(&command))
+        .enumerate()
+        .map(|(command_index, command)| command_to_delta(&command, command_index + 1))
         .fold(SubState::new(0, 0, 0), |state, delta| SubState::new(
             state.hpos + delta.hpos,
             state.depth + delta.hpos * state.aim,
@@ -17,17 +18,18 @@ fn move_submarine(input: impl Iterator<Item=String>) -> SubState {
         ))
 }
 
-fn command_to_delta(command: &str) -> SubState {
-    let split = command.split_whitespace().collect_vec();
-    let arg: u32 = String::from(split[1]).parse().unwrap();
-    if split[0] == "down" {
+fn command_to_delta(command: &str, line_number: usize) -> SubState {
+    let captures = match_pattern(command, line_number, "{} {}").unwrap_or_else(|error| panic!("{}", error));
+    let keyword = captures.str(0);
+    let arg: u32 = captures.parse(1).unwrap_or_else(|error| panic!("{}", error));
+    if keyword == "down" {
         SubState::new(0, 0, arg as i32)
-    } else if split[0] == "forward" {
+    } else if keyword == "forward" {
         SubState::new(arg as i32, 0, 0)
-    } else if split[0] == "up" {
+    } else if keyword == "up" {
         SubState::new(0, 0, -(arg as i32))
     } else {
-        panic!("Unknown command {}", split[0]);
+        panic!("Unknown command {}", keyword);
     }
 }
 
@@ -55,17 +57,24 @@ mod tests {
 
     #[test]
     fn down_command_to_delta() {
-        assert_eq!(command_to_delta("down 2"), SubState::new(0, 0, 2));
+        assert_eq!(command_to_delta("down 2", 1), SubState::new(0, 0, 2));
     }
 
     #[test]
     fn forward_command_to_delta() {
-        assert_eq!(command_to_delta("forward 3"), SubState::new(3, 0, 0));
+        assert_eq!(command_to_delta("forward 3", 1), SubState::new(3, 0, 0));
     }
 
     #[test]
     fn up_command_to_delta() {
-        assert_eq!(command_to_delta("up 2"), SubState::new(0, 0, -2));
+        assert_eq!(command_to_delta("up 2", 1), SubState::new(0, 0, -2));
+    }
+
+    #[test]
+    fn command_to_delta_with_extra_whitespace() {
+        assert_eq!(command_to_delta("forward  5", 1), SubState::new(5, 0, 0));
+        assert_eq!(command_to_delta("forward 5 ", 1), SubState::new(5, 0, 0));
+        assert_eq!(command_to_delta("down 3\r", 1), SubState::new(0, 0, 3));
     }
 
     #[test]
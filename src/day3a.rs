@@ -1,21 +1,31 @@
 use itertools::Itertools;
+use crate::day3format::BitFormat;
 
 #[allow(dead_code)]
 pub fn day3a(input: impl Iterator<Item=String>) -> (&'static str, i32) {
-    let (gamma, epsilon) = calc_gamma_and_epsilon(input);
+    day3a_with_format(input, BitFormat::Binary)
+}
+
+#[allow(dead_code)]
+pub fn day3a_with_format(input: impl Iterator<Item=String>, format: BitFormat) -> (&'static str, i32) {
+    let (gamma, epsilon) = calc_gamma_and_epsilon(input.map(|word| format.parse_bits(&word)));
     ("day3a", gamma * epsilon)
 }
 
-fn calc_gamma_and_epsilon(input: impl Iterator<Item=String>) -> (i32, i32) {
+fn calc_gamma_and_epsilon(input: impl Iterator<Item=Vec<bool>>) -> (i32, i32) {
     let (one_counts, num_values) = count_one_bits(input);
-    let gamma_bits = one_counts_to_gamma_bits(&one_counts, num_values);
+    one_counts_to_gamma_and_epsilon(&one_counts, num_values)
+}
+
+pub fn one_counts_to_gamma_and_epsilon(one_counts: &[i32], num_values: i32) -> (i32, i32) {
+    let gamma_bits = one_counts_to_gamma_bits(one_counts, num_values);
     let gamma = bits_to_decimal(&gamma_bits);
     let all_bit_set_as_decimal = (1 << gamma_bits.len()) - 1;
     (gamma, all_bit_set_as_decimal - gamma)
 }
 
-fn count_one_bits(input: impl Iterator<Item=String>) -> (Vec<i32>, i32) {
-    input.map(|bitstr| bitstr_to_bits(&bitstr))
+pub fn count_one_bits(input: impl Iterator<Item=Vec<bool>>) -> (Vec<i32>, i32) {
+    input
         .fold((vec![], 0), |(mut ones_counts, line_count), bits| {
             ones_counts.resize(bits.len(), 0);
             increment_elements(&mut ones_counts, &bits);
@@ -23,15 +33,12 @@ fn count_one_bits(input: impl Iterator<Item=String>) -> (Vec<i32>, i32) {
         })
 }
 
+#[cfg(test)]
 fn bitstr_to_bits(bitstr: &str) -> Vec<bool> {
-    let mut bits: Vec<bool> = Vec::with_capacity(bitstr.len());
-    for bitchar in bitstr.chars() {
-        bits.push(bitchar == '1');
-    }
-    bits
+    BitFormat::Binary.parse_bits(bitstr)
 }
 
-fn increment_elements(counts: &mut [i32], bits: &[bool]) {
+pub fn increment_elements(counts: &mut [i32], bits: &[bool]) {
     counts.iter_mut().enumerate()
         .filter(|(index, _element)| bits[*index])
         .for_each(|(_index, element)| *element += 1);
@@ -43,7 +50,7 @@ fn one_counts_to_gamma_bits(one_counts: &[i32], num_values: i32) -> Vec<bool> {
         .collect_vec()
 }
 
-fn bits_to_decimal(bits: &[bool]) -> i32 {
+pub fn bits_to_decimal(bits: &[bool]) -> i32 {
     bits
         .iter().rev().enumerate()
         .filter(|(_index, bit)| **bit)
@@ -55,6 +62,10 @@ mod tests {
     use crate::day3a::*;
     use crate::util::to_string_iter;
 
+    fn to_bits_iter(strs: Vec<&'static str>) -> impl Iterator<Item=Vec<bool>> {
+        strs.into_iter().map(bitstr_to_bits)
+    }
+
     #[test]
     fn example_bitstr_to_bits() {
         assert_eq!(bitstr_to_bits("1101010"), vec![true, true, false, true, false, true, false]);
@@ -63,13 +74,13 @@ mod tests {
     #[test]
     fn increment_ones_counts_by_example_bits() {
         let mut ones_counts = vec![0, 1, 2, 3, 4, 5, 6];
-        increment_elements(&mut ones_counts, &vec![true, true, false, true, false, true, false]);
+        increment_elements(&mut ones_counts, &[true, true, false, true, false, true, false]);
         assert_eq!(ones_counts, vec![1, 2, 2, 4, 4, 6, 6]);
     }
 
     #[test]
     fn count_one_bits_three_inputs() {
-        let input = to_string_iter(vec![
+        let input = to_bits_iter(vec![
             "11001010",
             "01010110",
             "11010100",
@@ -79,19 +90,19 @@ mod tests {
 
     #[test]
     fn calc_gamma_bits_three_inputs() {
-        let gamma_bits = one_counts_to_gamma_bits(&vec![2, 3, 0, 2, 1, 1, 2], 3);
+        let gamma_bits = one_counts_to_gamma_bits(&[2, 3, 0, 2, 1, 1, 2], 3);
         assert_eq!(gamma_bits, vec![true, true, false, true, false, false, true]);
     }
 
     #[test]
     fn example_bits_to_decimal() {
-        assert_eq!(bits_to_decimal(&vec![true, true, false, true, false, true]),
+        assert_eq!(bits_to_decimal(&[true, true, false, true, false, true]),
                    32 + 16 + 4 + 1)
     }
 
     #[test]
     fn count_gamma_and_epsilon_single_input() {
-        let input = to_string_iter(vec![
+        let input = to_bits_iter(vec![
             "110101",
         ]);
         assert_eq!(calc_gamma_and_epsilon(input), (32 + 16 + 4 + 1, 8 + 2));
@@ -123,4 +134,29 @@ mod tests {
         ]);
         assert_eq!(day3a(input), ("day3a", 198));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn example_input_as_hex() {
+        let input = to_string_iter(vec![
+            "04", "1E", "16", "17", "15", "0F", "07", "1C", "10", "19", "02", "0A",
+        ]);
+        assert_eq!(day3a_with_format(input, BitFormat::Hex { width: 5 }), ("day3a", 198));
+    }
+
+    #[test]
+    fn example_input_as_decimal() {
+        let input = to_string_iter(vec![
+            "4", "30", "22", "23", "21", "15", "7", "28", "16", "25", "2", "10",
+        ]);
+        assert_eq!(day3a_with_format(input, BitFormat::Decimal { width: 5 }), ("day3a", 198));
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid binary digit '2'")]
+    fn binary_input_rejects_other_digits() {
+        let input = to_string_iter(vec![
+            "10201",
+        ]);
+        day3a(input);
+    }
+}
@@ -1,36 +1,41 @@
 use itertools::Itertools;
+use crate::day3format::BitFormat;
 
 #[allow(dead_code)]
 pub fn day3b(input: impl Iterator<Item=String>) -> (&'static str, i32) {
-    let input_bitvecs = bitstrs_to_bitvecs(input);
+    day3b_with_format(input, BitFormat::Binary)
+}
+
+#[allow(dead_code)]
+pub fn day3b_with_format(input: impl Iterator<Item=String>, format: BitFormat) -> (&'static str, i32) {
+    let input_bitvecs = bitstrs_to_bitvecs(input, format);
     let og_rating = calc_rating(&input_bitvecs, &og_rating_selection_fn);
     let cs_rating = calc_rating(&input_bitvecs, &cs_rating_selection_fn);
     ("day3b", og_rating * cs_rating)
 }
 
-fn bitstrs_to_bitvecs(input_bitstrs: impl Iterator<Item=String>) -> Vec<Vec<bool>> {
+fn bitstrs_to_bitvecs(input_bitstrs: impl Iterator<Item=String>, format: BitFormat) -> Vec<Vec<bool>> {
     input_bitstrs
-        .map(|input_bitstr| bitstr_to_bitvec(&input_bitstr))
+        .map(|input_bitstr| format.parse_bits(&input_bitstr))
         .collect_vec()
 }
 
+#[cfg(test)]
 fn bitstr_to_bitvec(bitstr: &str) -> Vec<bool> {
-    bitstr.chars()
-        .map(|bitchar| bitchar == '1')
-        .collect_vec()
+    BitFormat::Binary.parse_bits(bitstr)
 }
 
 fn calc_rating<F>(input_bitvecs: &[Vec<bool>], selection_fn: &F) -> i32
     where F: Fn(usize, usize) -> bool
 {
-    bits_to_decimal(&find_rating_bits(&input_bitvecs, selection_fn))
+    bits_to_decimal(&find_rating_bits(input_bitvecs, selection_fn))
 }
 
-fn og_rating_selection_fn(true_bitvecs_len: usize, false_bitvecs_len: usize) -> bool {
+pub fn og_rating_selection_fn(true_bitvecs_len: usize, false_bitvecs_len: usize) -> bool {
     true_bitvecs_len >= false_bitvecs_len
 }
 
-fn cs_rating_selection_fn(true_bitvecs_len: usize, false_bitvecs_len: usize) -> bool {
+pub fn cs_rating_selection_fn(true_bitvecs_len: usize, false_bitvecs_len: usize) -> bool {
     true_bitvecs_len < false_bitvecs_len
 }
 
@@ -87,7 +92,7 @@ mod tests {
         let input_bitstrs = to_string_iter(vec![
             "11",
         ]);
-        assert_eq!(bitstrs_to_bitvecs(input_bitstrs), vec![vec![true, true]]);
+        assert_eq!(bitstrs_to_bitvecs(input_bitstrs, BitFormat::Binary), vec![vec![true, true]]);
     }
 
     #[test]
@@ -146,7 +151,7 @@ mod tests {
 
     #[test]
     fn example_bits_to_decimal() {
-        assert_eq!(bits_to_decimal(&vec![true, true, false, true, false, true]),
+        assert_eq!(bits_to_decimal(&[true, true, false, true, false, true]),
                    32 + 16 + 4 + 1)
     }
 
@@ -174,4 +179,29 @@ mod tests {
         ]);
         assert_eq!(day3b(input), ("day3b", 230));
     }
+
+    #[test]
+    fn single_hex_word_to_bitvec() {
+        let input_bitstrs = to_string_iter(vec![
+            "0x5",
+        ]);
+        assert_eq!(bitstrs_to_bitvecs(input_bitstrs, BitFormat::Hex { width: 4 }),
+                   vec![vec![false, true, false, true]]);
+    }
+
+    #[test]
+    fn example_input_as_hex() {
+        let input = to_string_iter(vec![
+            "04", "1E", "16", "17", "15", "0F", "07", "1C", "10", "19", "02", "0A",
+        ]);
+        assert_eq!(day3b_with_format(input, BitFormat::Hex { width: 5 }), ("day3b", 230));
+    }
+
+    #[test]
+    fn example_input_as_decimal() {
+        let input = to_string_iter(vec![
+            "4", "30", "22", "23", "21", "15", "7", "28", "16", "25", "2", "10",
+        ]);
+        assert_eq!(day3b_with_format(input, BitFormat::Decimal { width: 5 }), ("day3b", 230));
+    }
 }
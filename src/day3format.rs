@@ -0,0 +1,135 @@
+// The diagnostic values are built up in an i32, so words can't be any wider than this.
+pub const MAX_WIDTH: usize = 31;
+
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BitFormat {
+    Binary,
+    Hex { width: usize },
+    Decimal { width: usize },
+}
+
+impl BitFormat {
+    pub fn parse_bits(&self, word: &str) -> Vec<bool> {
+        match *self {
+            BitFormat::Binary => binary_to_bits(word),
+            BitFormat::Hex { width } => value_to_bits(parse_hex(word), width),
+            BitFormat::Decimal { width } => value_to_bits(parse_decimal(word), width),
+        }
+    }
+}
+
+fn binary_to_bits(bitstr: &str) -> Vec<bool> {
+    if bitstr.len() > MAX_WIDTH {
+        panic!("Binary number {:?} is wider than {} bits", bitstr, MAX_WIDTH);
+    }
+    bitstr.chars()
+        .map(|bitchar| match bitchar {
+            '0' => false,
+            '1' => true,
+            _ => panic!("Invalid binary digit {:?} in {:?}", bitchar, bitstr),
+        })
+        .collect()
+}
+
+fn parse_hex(word: &str) -> u64 {
+    let digits = word.strip_prefix("0x")
+        .or_else(|| word.strip_prefix("0X"))
+        .unwrap_or(word);
+    parse_digits(word, digits, 16)
+}
+
+fn parse_decimal(word: &str) -> u64 {
+    parse_digits(word, word, 10)
+}
+
+fn parse_digits(word: &str, digits: &str, radix: u32) -> u64 {
+    if digits.is_empty() || !digits.chars().all(|digit| digit.is_digit(radix)) {
+        panic!("Invalid base {} number {:?}", radix, word);
+    }
+    u64::from_str_radix(digits, radix)
+        .unwrap_or_else(|_| panic!("Base {} number {:?} is too large", radix, word))
+}
+
+fn value_to_bits(value: u64, width: usize) -> Vec<bool> {
+    assert!((1..=MAX_WIDTH).contains(&width), "Bit width {} is not between 1 and {}", width, MAX_WIDTH);
+    if value >> width != 0 {
+        panic!("Value {} does not fit in {} bits", value, width);
+    }
+    (0..width).rev()
+        .map(|bit_index| value & (1 << bit_index) != 0)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::day3format::*;
+
+    #[test]
+    fn example_binary_to_bits() {
+        assert_eq!(BitFormat::Binary.parse_bits("1101010"), vec![true, true, false, true, false, true, false]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid binary digit '2'")]
+    fn binary_rejects_other_digits() {
+        BitFormat::Binary.parse_bits("10201");
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid binary digit 'x'")]
+    fn binary_rejects_hex_prefix() {
+        BitFormat::Binary.parse_bits("0x1F");
+    }
+
+    #[test]
+    fn hex_to_bits_pads_to_width() {
+        assert_eq!(BitFormat::Hex { width: 6 }.parse_bits("1F"), vec![false, true, true, true, true, true]);
+    }
+
+    #[test]
+    fn hex_to_bits_accepts_prefix_and_either_case() {
+        assert_eq!(BitFormat::Hex { width: 8 }.parse_bits("0xaB"), BitFormat::Binary.parse_bits("10101011"));
+        assert_eq!(BitFormat::Hex { width: 8 }.parse_bits("0XAb"), BitFormat::Binary.parse_bits("10101011"));
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid base 16 number \"0x\"")]
+    fn hex_rejects_empty_digits() {
+        BitFormat::Hex { width: 8 }.parse_bits("0x");
+    }
+
+    #[test]
+    #[should_panic(expected = "Value 31 does not fit in 4 bits")]
+    fn hex_rejects_value_wider_than_width() {
+        BitFormat::Hex { width: 4 }.parse_bits("1F");
+    }
+
+    #[test]
+    fn decimal_to_bits_pads_to_width() {
+        assert_eq!(BitFormat::Decimal { width: 5 }.parse_bits("4"), vec![false, false, true, false, false]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid base 10 number \"+4\"")]
+    fn decimal_rejects_sign() {
+        BitFormat::Decimal { width: 5 }.parse_bits("+4");
+    }
+
+    #[test]
+    fn full_width_value_to_bits() {
+        assert_eq!(value_to_bits((1 << MAX_WIDTH) - 1, MAX_WIDTH), vec![true; MAX_WIDTH]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Bit width 32 is not between 1 and 31")]
+    fn hex_rejects_width_over_31() {
+        BitFormat::Hex { width: 32 }.parse_bits("1");
+    }
+
+    #[test]
+    #[should_panic(expected = "is wider than 31 bits")]
+    fn binary_rejects_more_than_31_digits() {
+        BitFormat::Binary.parse_bits(&"1".repeat(32));
+    }
+}
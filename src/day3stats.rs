@@ -0,0 +1,239 @@
+use itertools::Itertools;
+use crate::day3a::count_one_bits;
+use crate::day3format::BitFormat;
+
+#[allow(dead_code)]
+pub fn day3_report(input: impl Iterator<Item=String>, format: BitFormat) -> DiagnosticReport {
+    let bitvecs = input
+        .map(|word| format.parse_bits(&word))
+        .collect_vec();
+    DiagnosticReport::new(&bitvecs)
+}
+
+#[derive(Debug, PartialEq)]
+pub struct DiagnosticReport {
+    pub num_values: i32,
+    pub columns: Vec<ColumnStats>,
+    pub constant_columns: Vec<usize>,
+    pub correlated_pairs: Vec<(usize, usize)>,
+    pub anti_correlated_pairs: Vec<(usize, usize)>,
+}
+
+impl DiagnosticReport {
+    pub fn new(bitvecs: &[Vec<bool>]) -> Self {
+        // Columns only line up if every value is as wide as the first.
+        let width = bitvecs.first().map_or(0, |bits| bits.len());
+        if let Some((index, bits)) = bitvecs.iter().enumerate().find(|(_index, bits)| bits.len() != width) {
+            panic!("Line {} has {} bits, expected {} like line 1", index + 1, bits.len(), width);
+        }
+        let (one_counts, num_values) = count_one_bits(bitvecs.iter().cloned());
+        let columns = one_counts.iter().enumerate()
+            .map(|(column, &one_count)| ColumnStats::new(column, one_count, num_values))
+            .collect_vec();
+        let constant_columns = columns.iter()
+            .filter(|stats| stats.is_constant())
+            .map(|stats| stats.column)
+            .collect_vec();
+
+        let mut correlated_pairs = vec![];
+        let mut anti_correlated_pairs = vec![];
+        for (col1, col2) in (0..columns.len()).tuple_combinations() {
+            if columns[col1].is_constant() || columns[col2].is_constant() {
+                continue;
+            }
+            let num_disagreements = count_disagreements(bitvecs, col1, col2);
+            if num_disagreements == 0 {
+                correlated_pairs.push((col1, col2));
+            } else if num_disagreements == num_values {
+                anti_correlated_pairs.push((col1, col2));
+            }
+        }
+
+        DiagnosticReport {
+            num_values,
+            columns,
+            constant_columns,
+            correlated_pairs,
+            anti_correlated_pairs,
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn to_table(&self) -> String {
+        let mut table = String::from("column  ones  zeros  ratio  entropy\n");
+        for stats in &self.columns {
+            table.push_str(&format!("{:>6}  {:>4}  {:>5}  {:>5.3}  {:>7.3}\n",
+                                    stats.column, stats.one_count, stats.zero_count,
+                                    stats.one_ratio, stats.entropy));
+        }
+        table.push_str(&format!("constant columns: {}\n",
+                                self.constant_columns.iter().join(", ")));
+        table.push_str(&format!("correlated pairs: {}\n",
+                                pairs_to_string(&self.correlated_pairs)));
+        table.push_str(&format!("anti-correlated pairs: {}\n",
+                                pairs_to_string(&self.anti_correlated_pairs)));
+        table
+    }
+
+    // One record per line, for match_pattern and parse_list to read back. Lists that are
+    // empty are left out.
+    #[allow(dead_code)]
+    pub fn to_lines(&self) -> Vec<String> {
+        let mut lines = vec![format!("values {}", self.num_values)];
+        lines.extend(self.columns.iter()
+            .map(|stats| format!("column {} ones {} zeros {} ratio {} entropy {}",
+                                 stats.column, stats.one_count, stats.zero_count, stats.one_ratio, stats.entropy)));
+        if !self.constant_columns.is_empty() {
+            lines.push(format!("constant columns {}", self.constant_columns.iter().join(", ")));
+        }
+        if !self.correlated_pairs.is_empty() {
+            lines.push(format!("correlated pairs {}", pairs_to_string(&self.correlated_pairs)));
+        }
+        if !self.anti_correlated_pairs.is_empty() {
+            lines.push(format!("anti-correlated pairs {}", pairs_to_string(&self.anti_correlated_pairs)));
+        }
+        lines
+    }
+}
+
+fn count_disagreements(bitvecs: &[Vec<bool>], col1: usize, col2: usize) -> i32 {
+    bitvecs.iter()
+        .filter(|bits| bits[col1] != bits[col2])
+        .count() as i32
+}
+
+fn pairs_to_string(pairs: &[(usize, usize)]) -> String {
+    pairs.iter()
+        .map(|(col1, col2)| format!("{}-{}", col1, col2))
+        .join(", ")
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ColumnStats {
+    pub column: usize,
+    pub one_count: i32,
+    pub zero_count: i32,
+    pub one_ratio: f64,
+    pub entropy: f64,
+}
+
+impl ColumnStats {
+    pub fn new(column: usize, one_count: i32, num_values: i32) -> Self {
+        let one_ratio = if num_values == 0 { 0.0 } else { one_count as f64 / num_values as f64 };
+        ColumnStats {
+            column,
+            one_count,
+            zero_count: num_values - one_count,
+            one_ratio,
+            entropy: binary_entropy(one_ratio),
+        }
+    }
+
+    pub fn is_constant(&self) -> bool {
+        self.one_count == 0 || self.zero_count == 0
+    }
+}
+
+fn binary_entropy(p: f64) -> f64 {
+    if p <= 0.0 || p >= 1.0 {
+        0.0
+    } else {
+        -p * p.log2() - (1.0 - p) * (1.0 - p).log2()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::day3stats::*;
+    use crate::util::{match_pattern, to_string_iter};
+
+    #[test]
+    fn entropy_of_balanced_column_is_one() {
+        assert_eq!(binary_entropy(0.5), 1.0);
+    }
+
+    #[test]
+    fn entropy_of_constant_column_is_zero() {
+        assert_eq!(binary_entropy(0.0), 0.0);
+        assert_eq!(binary_entropy(1.0), 0.0);
+    }
+
+    #[test]
+    fn column_stats_counts_zeros() {
+        let stats = ColumnStats::new(2, 1, 4);
+        assert_eq!(stats.zero_count, 3);
+        assert_eq!(stats.one_ratio, 0.25);
+        assert!((stats.entropy - 0.8113).abs() < 0.0001);
+    }
+
+    #[test]
+    fn report_flags_constant_and_correlated_columns() {
+        let input = to_string_iter(vec![
+            "10110",
+            "00011",
+            "11100",
+            "01001",
+        ]);
+        let report = day3_report(input, BitFormat::Binary);
+        assert_eq!(report.num_values, 4);
+        assert_eq!(report.columns.iter().map(|stats| stats.one_count).collect_vec(), vec![2, 2, 2, 2, 2]);
+        assert_eq!(report.constant_columns, Vec::<usize>::new());
+        assert_eq!(report.correlated_pairs, vec![(0, 2)]);
+        assert_eq!(report.anti_correlated_pairs, vec![(0, 4), (1, 3), (2, 4)]);
+    }
+
+    #[test]
+    fn report_skips_constant_columns_when_pairing() {
+        let input = to_string_iter(vec![
+            "110",
+            "100",
+            "111",
+        ]);
+        let report = day3_report(input, BitFormat::Binary);
+        assert_eq!(report.constant_columns, vec![0]);
+        assert!(report.correlated_pairs.is_empty());
+        assert!(report.anti_correlated_pairs.is_empty());
+    }
+
+    #[test]
+    fn report_as_table() {
+        let input = to_string_iter(vec![
+            "10",
+            "11",
+        ]);
+        assert_eq!(day3_report(input, BitFormat::Binary).to_table(),
+                   "column  ones  zeros  ratio  entropy\n\
+                    \x20    0     2      0  1.000    0.000\n\
+                    \x20    1     1      1  0.500    1.000\n\
+                    constant columns: 0\n\
+                    correlated pairs: \n\
+                    anti-correlated pairs: \n");
+    }
+
+    #[test]
+    #[should_panic(expected = "Line 3 has 2 bits, expected 3 like line 1")]
+    fn report_rejects_ragged_rows() {
+        day3_report(to_string_iter(vec!["101", "011", "10", "110"]), BitFormat::Binary);
+    }
+
+    #[test]
+    fn report_as_lines() {
+        let input = to_string_iter(vec![
+            "100",
+            "010",
+        ]);
+        let lines = day3_report(input, BitFormat::Binary).to_lines();
+        assert_eq!(lines, vec![
+            "values 2",
+            "column 0 ones 1 zeros 1 ratio 0.5 entropy 1",
+            "column 1 ones 1 zeros 1 ratio 0.5 entropy 1",
+            "column 2 ones 0 zeros 2 ratio 0 entropy 0",
+            "constant columns 2",
+            "anti-correlated pairs 0-1",
+        ]);
+        let captures = match_pattern(&lines[1], 2, "column {} ones {} zeros {} ratio {} entropy {}").unwrap();
+        assert_eq!((captures.parse::<usize>(0), captures.parse::<f64>(3)), (Ok(0), Ok(0.5)));
+        let captures = match_pattern(&lines[4], 5, "constant columns {}").unwrap();
+        assert_eq!(captures.parse_list::<usize>(0, ","), Ok(vec![2]));
+    }
+}
@@ -0,0 +1,271 @@
+use crate::day3a::{bits_to_decimal, increment_elements, one_counts_to_gamma_and_epsilon};
+use crate::day3b::{cs_rating_selection_fn, og_rating_selection_fn};
+use crate::day3format::BitFormat;
+
+#[allow(dead_code)]
+pub fn day3_stream(input: impl Iterator<Item=String>, format: BitFormat, report_every: usize) -> impl Iterator<Item=InterimReport> {
+    assert!(report_every > 0, "Report interval must be at least one line");
+    StreamReports {
+        input,
+        format,
+        report_every,
+        diagnostic: StreamingDiagnostic::new(),
+        finished: false,
+    }
+}
+
+struct StreamReports<I> {
+    input: I,
+    format: BitFormat,
+    report_every: usize,
+    diagnostic: StreamingDiagnostic,
+    finished: bool,
+}
+
+impl<I> Iterator for StreamReports<I>
+    where I: Iterator<Item=String>
+{
+    type Item = InterimReport;
+
+    fn next(&mut self) -> Option<InterimReport> {
+        if self.finished {
+            return None;
+        }
+        for word in self.input.by_ref() {
+            self.diagnostic.push(&self.format.parse_bits(&word));
+            if self.diagnostic.num_values().is_multiple_of(self.report_every) {
+                return Some(self.diagnostic.report());
+            }
+        }
+        self.finished = true;
+        if !self.diagnostic.num_values().is_multiple_of(self.report_every) {
+            Some(self.diagnostic.report())
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct InterimReport {
+    pub num_values: usize,
+    pub gamma: i32,
+    pub epsilon: i32,
+    pub og_rating: i32,
+    pub cs_rating: i32,
+}
+
+pub struct StreamingDiagnostic {
+    one_counts: Vec<i32>,
+    trie: CountingTrie,
+}
+
+impl StreamingDiagnostic {
+    pub fn new() -> Self {
+        StreamingDiagnostic {
+            one_counts: vec![],
+            trie: CountingTrie::new(),
+        }
+    }
+
+    pub fn push(&mut self, bits: &[bool]) {
+        if self.one_counts.len() < bits.len() {
+            self.one_counts.resize(bits.len(), 0);
+        }
+        increment_elements(&mut self.one_counts, bits);
+        self.trie.insert(bits);
+    }
+
+    pub fn num_values(&self) -> usize {
+        self.trie.count(0) as usize
+    }
+
+    pub fn gamma_and_epsilon(&self) -> (i32, i32) {
+        one_counts_to_gamma_and_epsilon(&self.one_counts, self.num_values() as i32)
+    }
+
+    pub fn og_rating(&self) -> i32 {
+        bits_to_decimal(&self.trie.find_rating_bits(&og_rating_selection_fn))
+    }
+
+    pub fn cs_rating(&self) -> i32 {
+        bits_to_decimal(&self.trie.find_rating_bits(&cs_rating_selection_fn))
+    }
+
+    pub fn report(&self) -> InterimReport {
+        let (gamma, epsilon) = self.gamma_and_epsilon();
+        InterimReport {
+            num_values: self.num_values(),
+            gamma,
+            epsilon,
+            og_rating: self.og_rating(),
+            cs_rating: self.cs_rating(),
+        }
+    }
+}
+
+// Node 0 is the root, so a child index of 0 means "no child".
+struct CountingTrie {
+    nodes: Vec<TrieNode>,
+}
+
+struct TrieNode {
+    count: u32,
+    children: [usize; 2],
+}
+
+impl CountingTrie {
+    pub fn new() -> Self {
+        CountingTrie {
+            nodes: vec![TrieNode { count: 0, children: [0, 0] }],
+        }
+    }
+
+    pub fn insert(&mut self, bits: &[bool]) {
+        let mut node_index = 0;
+        self.nodes[node_index].count += 1;
+        for &bit in bits {
+            let child_index = match self.nodes[node_index].children[bit as usize] {
+                0 => self.add_child(node_index, bit),
+                child_index => child_index,
+            };
+            node_index = child_index;
+            self.nodes[node_index].count += 1;
+        }
+    }
+
+    fn add_child(&mut self, node_index: usize, bit: bool) -> usize {
+        self.nodes.push(TrieNode { count: 0, children: [0, 0] });
+        let child_index = self.nodes.len() - 1;
+        self.nodes[node_index].children[bit as usize] = child_index;
+        child_index
+    }
+
+    pub fn count(&self, node_index: usize) -> u32 {
+        self.nodes[node_index].count
+    }
+
+    fn child_count(&self, node_index: usize, bit: bool) -> usize {
+        match self.nodes[node_index].children[bit as usize] {
+            0 => 0,
+            child_index => self.count(child_index) as usize,
+        }
+    }
+
+    pub fn find_rating_bits<F>(&self, selection_fn: &F) -> Vec<bool>
+        where F: Fn(usize, usize) -> bool
+    {
+        let mut bits = vec![];
+        let mut node_index = 0;
+        loop {
+            let true_count = self.child_count(node_index, true);
+            let false_count = self.child_count(node_index, false);
+            let bit = if true_count == 0 || false_count == 0 {
+                true_count > 0
+            } else {
+                selection_fn(true_count, false_count)
+            };
+            match self.nodes[node_index].children[bit as usize] {
+                0 => return bits,
+                child_index => {
+                    bits.push(bit);
+                    node_index = child_index;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use itertools::Itertools;
+    use crate::day3stream::*;
+    use crate::util::to_string_iter;
+
+    fn example_input() -> impl Iterator<Item=String> {
+        to_string_iter(vec![
+            "00100",
+            "11110",
+            "10110",
+            "10111",
+            "10101",
+            "01111",
+            "00111",
+            "11100",
+            "10000",
+            "11001",
+            "00010",
+            "01010",
+        ])
+    }
+
+    fn diagnostic_from(bitstrs: Vec<&'static str>) -> StreamingDiagnostic {
+        let mut diagnostic = StreamingDiagnostic::new();
+        bitstrs.into_iter().for_each(|bitstr| diagnostic.push(&BitFormat::Binary.parse_bits(bitstr)));
+        diagnostic
+    }
+
+    #[test]
+    fn trie_counts_shared_prefixes() {
+        let mut trie = CountingTrie::new();
+        trie.insert(&[true, false]);
+        trie.insert(&[true, true]);
+        trie.insert(&[false, true]);
+        assert_eq!(trie.count(0), 3);
+        assert_eq!(trie.child_count(0, true), 2);
+        assert_eq!(trie.child_count(0, false), 1);
+        assert_eq!(trie.nodes.len(), 6);
+    }
+
+    #[test]
+    fn single_value_is_both_ratings() {
+        let diagnostic = diagnostic_from(vec!["0100"]);
+        assert_eq!(diagnostic.og_rating(), 4);
+        assert_eq!(diagnostic.cs_rating(), 4);
+    }
+
+    #[test]
+    fn og_rating_prefers_true_bit_if_equal_numbers_of_true_and_false() {
+        let diagnostic = diagnostic_from(vec!["01", "10"]);
+        assert_eq!(diagnostic.og_rating(), 2);
+        assert_eq!(diagnostic.cs_rating(), 1);
+    }
+
+    #[test]
+    fn cs_rating_keeps_values_that_all_share_a_bit() {
+        let diagnostic = diagnostic_from(vec!["100", "101", "111"]);
+        assert_eq!(diagnostic.cs_rating(), 7);
+    }
+
+    #[test]
+    fn example_input_final_report_matches_day3a_and_day3b() {
+        let mut diagnostic = StreamingDiagnostic::new();
+        example_input().for_each(|bitstr| diagnostic.push(&BitFormat::Binary.parse_bits(&bitstr)));
+        assert_eq!(diagnostic.report(), InterimReport {
+            num_values: 12,
+            gamma: 22,
+            epsilon: 9,
+            og_rating: 23,
+            cs_rating: 10,
+        });
+    }
+
+    #[test]
+    fn stream_reports_every_n_lines_and_at_end() {
+        let reports = day3_stream(example_input(), BitFormat::Binary, 5).collect_vec();
+        assert_eq!(reports.iter().map(|report| report.num_values).collect_vec(), vec![5, 10, 12]);
+        assert_eq!(reports[0].og_rating, 0b10111);
+        assert_eq!((reports[2].gamma, reports[2].epsilon), (22, 9));
+    }
+
+    #[test]
+    fn stream_does_not_repeat_final_report() {
+        let reports = day3_stream(example_input(), BitFormat::Binary, 6).collect_vec();
+        assert_eq!(reports.iter().map(|report| report.num_values).collect_vec(), vec![6, 12]);
+    }
+
+    #[test]
+    fn stream_of_empty_input_has_no_reports() {
+        assert_eq!(day3_stream(to_string_iter(vec![]), BitFormat::Binary, 3).count(), 0);
+    }
+}
@@ -1,14 +1,16 @@
 use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
 use std::vec;
 use itertools::Itertools;
+use crate::util::{Block, Grid, parse_list, parse_table, ParseError, read_blocks};
 
 #[allow(dead_code)]
 pub fn day4a(mut input: impl Iterator<Item=String>) -> (&'static str, i32) {
-    let numbers = parse_numbers_csv(&input.next().unwrap());
+    let numbers = parse_numbers_csv(&input.next().unwrap(), 1).unwrap_or_else(|error| panic!("{}", error));
     let mut boards = read_boards(input);
-    if let Some((winning_board_index, winning_number)) = play_to_first_winning_board(&mut boards, &numbers) {
-        let winning_score = boards[winning_board_index].sum_unmarked_numbers();
-        let answer = winning_score * winning_number;
+    if let Some((winning_board_index, winning_number)) = play_to_first_winning_board(&mut boards, &numbers, ROWS_AND_COLUMNS) {
+        let answer = boards[winning_board_index].sum_unmarked_numbers() * winning_number;
         ("day4a", answer as i32)
     } else {
         panic!("No winning board");
@@ -17,85 +19,56 @@ pub fn day4a(mut input: impl Iterator<Item=String>) -> (&'static str, i32) {
 
 #[allow(dead_code)]
 pub fn day4b(mut input: impl Iterator<Item=String>) -> (&'static str, i32) {
-    let numbers = parse_numbers_csv(&input.next().unwrap());
+    let numbers = parse_numbers_csv(&input.next().unwrap(), 1).unwrap_or_else(|error| panic!("{}", error));
     let mut boards = read_boards(input);
-    if let Some((winning_board_index, winning_number)) = play_to_last_winning_board(&mut boards, &numbers) {
-        let winning_score = boards[winning_board_index].sum_unmarked_numbers();
-        let answer = winning_score * winning_number;
+    if let Some((winning_board_index, winning_number)) = play_to_last_winning_board(&mut boards, &numbers, ROWS_AND_COLUMNS) {
+        let answer = boards[winning_board_index].sum_unmarked_numbers() * winning_number;
         ("day4b", answer as i32)
     } else {
         panic!("No winning board");
     }
 }
 
-fn parse_numbers_csv(numbers_csv: &str) -> Vec<u32> {
-    numbers_csv
-        .split(",")
-        .map(|digits| digits.parse().unwrap())
-        .collect_vec()
+pub fn parse_numbers_csv(numbers_csv: &str, line_number: usize) -> Result<Vec<u32>, ParseError> {
+    parse_list(numbers_csv, line_number, ",")
 }
 
-fn read_boards(mut input: impl Iterator<Item=String>) -> Vec<Board> {
-    let mut boards = vec![];
-    while input.next().is_some() {
-        boards.push(read_board(&mut input));
-    }
-    boards
+// The boards follow the line of drawn numbers, so they start on line 2 of the input.
+pub fn read_boards(input: impl Iterator<Item=String>) -> Vec<Board> {
+    try_read_boards(input, 2).unwrap_or_else(|error| panic!("{}", error))
 }
 
-fn read_board(input: &mut impl Iterator<Item=String>) -> Board {
-    let mut numbers: Vec<u32> = Vec::with_capacity(25);
-    for _i in 1..=5 {
-        input.next().unwrap()
-            .split_whitespace()
-            .map(|digits| digits.parse().unwrap())
-            .for_each(|num| numbers.push(num));
-    }
-    Board::new(&numbers)
+pub fn try_read_boards(input: impl Iterator<Item=String>, first_line_number: usize) -> Result<Vec<Board>, ParseError> {
+    read_blocks(input, first_line_number).into_iter()
+        .map(read_board)
+        .collect()
 }
 
-fn play_to_first_winning_board(boards: &mut Vec<Board>, numbers: &[u32]) -> Option<(usize, u32)> {
-    let cell_indexes = build_cell_indexes(boards);
-    for &number in numbers {
-        let winning_board_indexes = play_number_on_all_boards(number, boards, &cell_indexes);
-        if !winning_board_indexes.is_empty() {
-            return Some((winning_board_indexes[0], number));
-        }
-    }
-    None
+pub fn read_board(block: Block) -> Result<Board, ParseError> {
+    let cells = parse_table::<Cell>(block.lines.into_iter(), block.first_line_number)?;
+    Ok(Board::from_grid(&cells))
 }
 
-fn play_to_last_winning_board(boards: &mut Vec<Board>, numbers: &[u32]) -> Option<(usize, u32)> {
-    let cell_indexes = build_cell_indexes(boards);
-    let mut num_boards_in_play = boards.len();
-    for &number in numbers {
-        let winning_board_indexes = play_number_on_all_boards(number, boards, &cell_indexes);
-        num_boards_in_play -= winning_board_indexes.len();
-        if num_boards_in_play == 0 {
-            return Some((winning_board_indexes[0], number));
-        }
-    }
-    None
-}
-
-fn build_cell_indexes(boards: &[Board]) -> HashMap<u32, Vec<(usize, usize)>> {
+pub fn build_cell_indexes(boards: &[Board]) -> HashMap<u32, Vec<(usize, usize)>> {
     let mut cell_indexes = HashMap::new();
     for (board_index, board) in boards.iter().enumerate() {
-        for (cell_index, cell) in board.cells.iter().enumerate() {
-            cell_indexes
-                .entry(cell.number)
-                .or_insert(vec![])
-                .push((board_index, cell_index));
+        for (cell_index, number) in board.numbers().enumerate() {
+            if let Some(number) = number {
+                cell_indexes
+                    .entry(number)
+                    .or_insert(vec![])
+                    .push((board_index, cell_index));
+            }
         }
     }
     cell_indexes
 }
 
-fn play_number_on_all_boards(number: u32, boards: &mut Vec<Board>, cell_indexes: &HashMap<u32, Vec<(usize, usize)>>) -> Vec<usize> {
+pub fn play_number_on_all_boards(number: u32, boards: &mut [Board], cell_indexes: &HashMap<u32, Vec<(usize, usize)>>, patterns: &[WinPattern]) -> Vec<usize> {
     let mut winning_board_indexes = vec![];
     if let Some(indexes) = cell_indexes.get(&number) {
         for &(board_index, cell_index) in indexes {
-            if boards[board_index].play_cell(cell_index) {
+            if boards[board_index].play_cell(cell_index, patterns).is_some() {
                 winning_board_indexes.push(board_index);
             }
         }
@@ -103,64 +76,502 @@ fn play_number_on_all_boards(number: u32, boards: &mut Vec<Board>, cell_indexes:
     winning_board_indexes
 }
 
+// Free and pre-marked cells can complete a pattern before any of its numbers is drawn, which
+// checking only the lines through each drawn cell would miss. Every line of every pattern is
+// checked once, on the first draw, and the boards already complete win on it.
+pub fn claim_completed_boards(boards: &mut [Board], patterns: &[WinPattern]) -> Vec<usize> {
+    (0..boards.len())
+        .filter(|&board_index| boards[board_index].claim_completed_pattern(patterns).is_some())
+        .collect_vec()
+}
+
+// Plays every number until every board has won, recording each board's win.
+pub fn play_all_boards(boards: &mut [Board], numbers: &[u32], patterns: &[WinPattern]) -> GameResults {
+    play_boards(boards, numbers, patterns, false)
+}
+
+// Leaves the boards as they were when the first board won, so its score can be read off them.
+pub fn play_to_first_winning_board(boards: &mut [Board], numbers: &[u32], patterns: &[WinPattern]) -> Option<(usize, u32)> {
+    play_boards(boards, numbers, patterns, true).first_winner()
+        .map(|winner| (winner.board_index, winner.win.unwrap().number))
+}
+
+// None unless every board wins.
+pub fn play_to_last_winning_board(boards: &mut [Board], numbers: &[u32], patterns: &[WinPattern]) -> Option<(usize, u32)> {
+    let results = play_all_boards(boards, numbers, patterns);
+    if results.never_won().next().is_some() {
+        return None;
+    }
+    results.last_winner().map(|winner| (winner.board_index, winner.win.unwrap().number))
+}
+
+fn play_boards(boards: &mut [Board], numbers: &[u32], patterns: &[WinPattern], stop_at_first_win: bool) -> GameResults {
+    let cell_indexes = build_cell_indexes(boards);
+    let mut wins: Vec<Option<BoardWin>> = vec![None; boards.len()];
+    let mut win_order = vec![];
+    for (draw_index, &number) in numbers.iter().enumerate() {
+        let mut winners = play_number_on_all_boards(number, boards, &cell_indexes, patterns);
+        if draw_index == 0 {
+            winners.extend(claim_completed_boards(boards, patterns));
+            winners.sort_unstable();
+        }
+        for board_index in winners {
+            wins[board_index] = Some(BoardWin {
+                draw_index,
+                number,
+                score: boards[board_index].sum_unmarked_numbers() * number,
+            });
+            win_order.push(board_index);
+        }
+        if win_order.len() == boards.len() || (stop_at_first_win && !win_order.is_empty()) {
+            break;
+        }
+    }
+
+    let results = wins.into_iter().enumerate()
+        .map(|(board_index, win)| BoardResult {
+            board_index,
+            win,
+            num_marked: boards[board_index].num_marked(),
+        })
+        .collect_vec();
+    GameResults { results, win_order }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BoardWin {
+    pub draw_index: usize,
+    pub number: u32,
+    pub score: u32,
+}
+
 #[derive(Debug, PartialEq)]
-struct Board {
-    cells: Vec<Cell>,
-    bingo: bool,
+pub struct BoardResult {
+    pub board_index: usize,
+    pub win: Option<BoardWin>,
+    pub num_marked: usize,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct GameResults {
+    results: Vec<BoardResult>,
+    win_order: Vec<usize>,
+}
+
+#[allow(dead_code)]
+impl GameResults {
+    pub fn board(&self, board_index: usize) -> &BoardResult {
+        &self.results[board_index]
+    }
+
+    pub fn boards(&self) -> impl Iterator<Item=&BoardResult> {
+        self.results.iter()
+    }
+
+    pub fn winners(&self) -> impl Iterator<Item=&BoardResult> {
+        self.win_order.iter().map(|&board_index| &self.results[board_index])
+    }
+
+    pub fn never_won(&self) -> impl Iterator<Item=&BoardResult> {
+        self.results.iter().filter(|result| result.win.is_none())
+    }
+
+    pub fn num_winners(&self) -> usize {
+        self.win_order.len()
+    }
+
+    pub fn nth_winner(&self, n: usize) -> Option<&BoardResult> {
+        self.win_order.get(n).map(|&board_index| &self.results[board_index])
+    }
+
+    pub fn first_winner(&self) -> Option<&BoardResult> {
+        self.nth_winner(0)
+    }
+
+    // Boards that win on the same draw are in board order, and the first of those on the
+    // last winning draw counts as the last winner.
+    pub fn last_winner(&self) -> Option<&BoardResult> {
+        let last_draw_index = self.winners().last()?.win?.draw_index;
+        self.winners().find(|result| result.win.is_some_and(|win| win.draw_index == last_draw_index))
+    }
+
+    pub fn median_winner(&self) -> Option<&BoardResult> {
+        self.num_winners().checked_sub(1).and_then(|n| self.nth_winner(n / 2))
+    }
+}
+
+// One bit per cell, in the board's row-by-row cell order, in as many words as the board
+// needs. The last word is never zero, so equal masks compare equal.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CellMask {
+    words: Vec<u128>,
+}
+
+const WORD_BITS: usize = u128::BITS as usize;
+
+impl CellMask {
+    pub fn from_indexes(indexes: impl IntoIterator<Item=usize>) -> Self {
+        let mut mask = CellMask::default();
+        for index in indexes {
+            mask.insert(index);
+        }
+        mask
+    }
+
+    pub fn insert(&mut self, index: usize) {
+        let (word_index, bit) = (index / WORD_BITS, index % WORD_BITS);
+        if word_index >= self.words.len() {
+            self.words.resize(word_index + 1, 0);
+        }
+        self.words[word_index] |= 1 << bit;
+    }
+
+    pub fn contains(&self, index: usize) -> bool {
+        self.word(index / WORD_BITS) & (1 << (index % WORD_BITS)) != 0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.is_empty()
+    }
+
+    pub fn count(&self) -> usize {
+        self.words.iter().map(|word| word.count_ones() as usize).sum()
+    }
+
+    pub fn indexes(&self) -> impl Iterator<Item=usize> + '_ {
+        self.words.iter().enumerate()
+            .flat_map(|(word_index, &word)| (0..WORD_BITS)
+                .filter(move |bit| word & (1 << bit) != 0)
+                .map(move |bit| word_index * WORD_BITS + bit))
+    }
+
+    pub fn union(&self, other: &CellMask) -> CellMask {
+        let words = (0..self.words.len().max(other.words.len()))
+            .map(|word_index| self.word(word_index) | other.word(word_index))
+            .collect_vec();
+        CellMask { words }
+    }
+
+    pub fn shifted(&self, shift: usize) -> CellMask {
+        CellMask::from_indexes(self.indexes().map(|index| index + shift))
+    }
+
+    // Whether every cell in other is also in this mask.
+    pub fn covers(&self, other: &CellMask) -> bool {
+        self.covers_shifted(other, 0)
+    }
+
+    // As covers, with other moved along by shift cells, without building the moved mask.
+    pub fn covers_shifted(&self, other: &CellMask, shift: usize) -> bool {
+        let (word_shift, bit_shift) = (shift / WORD_BITS, shift % WORD_BITS);
+        other.words.iter().enumerate().all(|(word_index, &word)| {
+            let low = word << bit_shift;
+            let high = if bit_shift == 0 { 0 } else { word >> (WORD_BITS - bit_shift) };
+            let target = word_index + word_shift;
+            self.word(target) & low == low && self.word(target + 1) & high == high
+        })
+    }
+
+    fn word(&self, word_index: usize) -> u128 {
+        self.words.get(word_index).copied().unwrap_or(0)
+    }
+}
+
+// Every mask is built once, so that checking any pattern is a single AND and compare. Row
+// and column masks are shifted along from the first row and column.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Board {
+    numbers: Grid<Option<u32>>,
+    marked: CellMask,
+    first_row_mask: CellMask,
+    first_column_mask: CellMask,
+    main_diagonal_mask: CellMask,
+    anti_diagonal_mask: CellMask,
+    corners_mask: CellMask,
+    all_cells_mask: CellMask,
+    win: Option<Box<(WinPattern, CellMask)>>,
 }
 
 impl Board {
+    #[cfg(test)]
     pub fn new(numbers: &[u32]) -> Self {
-        assert_eq!(numbers.len(), 5 * 5);
+        Board::with_size(5, 5, numbers)
+    }
+
+    #[cfg(test)]
+    pub fn with_size(width: usize, height: usize, numbers: &[u32]) -> Self {
+        Board::from_cells(width, height, numbers.iter()
+            .map(|&num| Cell::new(num, false))
+            .collect_vec())
+    }
+
+    #[allow(dead_code)]
+    pub fn from_cells(width: usize, height: usize, cells: Vec<Cell>) -> Self {
+        Board::from_grid(&Grid::from_cells(width, height, cells))
+    }
+
+    pub fn from_grid(cells: &Grid<Cell>) -> Self {
+        let numbers = cells.map(|cell| cell.number);
+        let (width, height) = (numbers.width(), numbers.height());
+        // Diagonals only exist on square boards; an empty mask never completes.
+        let (main_diagonal_mask, anti_diagonal_mask) = if numbers.is_square() {
+            (CellMask::from_indexes(numbers.main_diagonal_indexes()), CellMask::from_indexes(numbers.anti_diagonal_indexes()))
+        } else {
+            (CellMask::default(), CellMask::default())
+        };
+        let corners_mask = if numbers.is_empty() {
+            CellMask::default()
+        } else {
+            let (last_x, last_y) = (width - 1, height - 1);
+            CellMask::from_indexes([(0, 0), (last_x, 0), (0, last_y), (last_x, last_y)].into_iter()
+                .map(|(x, y)| numbers.index_of(x, y)))
+        };
         Board {
-            cells: numbers.iter()
-                .map(|&num| Cell::new(num, false))
-                .collect_vec(),
-            bingo: false,
+            marked: CellMask::from_indexes(cells.cells().positions(|cell| cell.is_marked)),
+            first_row_mask: CellMask::from_indexes(0..width),
+            first_column_mask: CellMask::from_indexes((0..height).map(|y| numbers.index_of(0, y))),
+            main_diagonal_mask,
+            anti_diagonal_mask,
+            corners_mask,
+            all_cells_mask: CellMask::from_indexes(0..numbers.len()),
+            numbers,
+            win: None,
         }
     }
 
-    pub fn play_cell(&mut self, cell_index: usize) -> bool {
-        if self.bingo {
-            return false;
+    #[inline]
+    pub fn play_cell(&mut self, cell_index: usize, patterns: &[WinPattern]) -> Option<WinPattern> {
+        if self.has_won() {
+            return None;
         }
 
         self.mark_cell(cell_index);
-        self.bingo = self.is_cell_in_fully_marked_row(cell_index) || self.is_cell_in_fully_marked_column(cell_index);
-        self.bingo
+        for pattern in patterns {
+            if let Some(line) = pattern.completed_line(self, cell_index) {
+                self.win = Some(Box::new((pattern.clone(), line)));
+                return Some(pattern.clone());
+            }
+        }
+        None
+    }
+
+    // Checks every line of every pattern rather than just those through one cell.
+    pub fn claim_completed_pattern(&mut self, patterns: &[WinPattern]) -> Option<WinPattern> {
+        if self.has_won() {
+            return None;
+        }
+
+        for pattern in patterns {
+            if let Some(line) = pattern.completed_anywhere(self) {
+                self.win = Some(Box::new((pattern.clone(), line)));
+                return Some(pattern.clone());
+            }
+        }
+        None
+    }
+
+    pub fn has_won(&self) -> bool {
+        self.win.is_some()
+    }
+
+    pub fn winning_pattern(&self) -> Option<&WinPattern> {
+        self.win.as_ref().map(|win| &win.0)
+    }
+
+    pub fn winning_line(&self) -> Option<Vec<usize>> {
+        self.win.as_ref().map(|win| win.1.indexes().collect_vec())
+    }
+
+    pub fn record_win(&mut self, pattern: WinPattern, line: &[usize]) {
+        self.win = Some(Box::new((pattern, CellMask::from_indexes(line.iter().copied()))));
     }
 
     pub fn mark_cell(&mut self, cell_index: usize) {
-        self.cells[cell_index].is_marked = true;
+        self.marked.insert(cell_index);
+    }
+
+    pub fn is_marked(&self, cell_index: usize) -> bool {
+        self.marked.contains(cell_index)
     }
 
+    #[allow(dead_code)]
+    pub fn cell(&self, cell_index: usize) -> Cell {
+        Cell {
+            number: self.numbers[self.numbers.position_of(cell_index)],
+            is_marked: self.is_marked(cell_index),
+        }
+    }
+
+    pub fn numbers(&self) -> impl Iterator<Item=Option<u32>> + '_ {
+        self.numbers.cells().copied()
+    }
+
+    pub fn number_grid(&self) -> &Grid<Option<u32>> {
+        &self.numbers
+    }
+
+    #[cfg(test)]
     pub fn is_cell_in_fully_marked_row(&self, cell_index: usize) -> bool {
-        let row_first_index = (cell_index / 5) * 5;
-        (row_first_index..row_first_index + 5).all(|index| self.cells[index].is_marked)
+        self.marked.covers_shifted(&self.first_row_mask, self.row_shift(cell_index))
     }
 
+    #[cfg(test)]
     pub fn is_cell_in_fully_marked_column(&self, cell_index: usize) -> bool {
-        let col_first_index = cell_index % 5;
-        (0..=4).map(|i| col_first_index + i * 5).all(|index| self.cells[index].is_marked)
+        self.marked.covers_shifted(&self.first_column_mask, self.column_shift(cell_index))
+    }
+
+    fn row_shift(&self, cell_index: usize) -> usize {
+        let (_x, y) = self.numbers.position_of(cell_index);
+        self.numbers.index_of(0, y)
+    }
+
+    fn column_shift(&self, cell_index: usize) -> usize {
+        let (x, _y) = self.numbers.position_of(cell_index);
+        x
+    }
+
+    pub fn is_fully_marked(&self, mask: &CellMask) -> bool {
+        self.marked.covers(mask)
+    }
+
+    pub fn completed_line(&self, line: &CellMask, cell_index: usize) -> Option<CellMask> {
+        (line.contains(cell_index) && self.is_fully_marked(line)).then(|| line.clone())
+    }
+
+    // The row or column through a cell always contains it, so only the marks need checking.
+    fn completed_shifted_line(&self, first_line: &CellMask, shift: usize) -> Option<CellMask> {
+        self.marked.covers_shifted(first_line, shift).then(|| first_line.shifted(shift))
+    }
+
+    // An empty line, such as the diagonal of a rectangular board, is never complete.
+    fn completed_line_anywhere(&self, line: &CellMask) -> Option<CellMask> {
+        (!line.is_empty() && self.is_fully_marked(line)).then(|| line.clone())
+    }
+
+    #[allow(dead_code)]
+    pub fn num_cells(&self) -> usize {
+        self.numbers.len()
+    }
+
+    pub fn num_marked(&self) -> usize {
+        self.marked.count()
     }
 
     pub fn sum_unmarked_numbers(&self) -> u32 {
-        self.cells.iter()
-            .filter(|cell| !cell.is_marked)
-            .map(|cell| cell.number)
+        self.numbers.cells().enumerate()
+            .filter(|&(cell_index, _number)| !self.is_marked(cell_index))
+            .filter_map(|(_cell_index, number)| *number)
             .sum()
     }
 }
 
-#[derive(Debug, PartialEq)]
-struct Cell {
-    number: u32,
+pub const ROWS_AND_COLUMNS: &[WinPattern] = &[WinPattern::Row, WinPattern::Column];
+
+#[allow(dead_code)]
+#[derive(Debug, Clone, PartialEq)]
+pub enum WinPattern {
+    Row,
+    Column,
+    Diagonal,
+    FourCorners,
+    X,
+    Blackout,
+    // The cells to cover, as built by WinPattern::mask.
+    Mask(CellMask),
+}
+
+impl WinPattern {
+    // One flag per cell, in row-by-row order.
+    #[allow(dead_code)]
+    pub fn mask(cells: &[bool]) -> Self {
+        WinPattern::Mask(CellMask::from_indexes(cells.iter().positions(|&in_mask| in_mask)))
+    }
+
+    #[inline]
+    pub fn completed_line(&self, board: &Board, cell_index: usize) -> Option<CellMask> {
+        match self {
+            WinPattern::Row => board.completed_shifted_line(&board.first_row_mask, board.row_shift(cell_index)),
+            WinPattern::Column => board.completed_shifted_line(&board.first_column_mask, board.column_shift(cell_index)),
+            WinPattern::Diagonal => board.completed_line(&board.main_diagonal_mask, cell_index)
+                .or_else(|| board.completed_line(&board.anti_diagonal_mask, cell_index)),
+            WinPattern::FourCorners => board.completed_line(&board.corners_mask, cell_index),
+            WinPattern::X => board.completed_line(&board.main_diagonal_mask.union(&board.anti_diagonal_mask), cell_index),
+            WinPattern::Blackout => board.completed_line(&board.all_cells_mask, cell_index),
+            WinPattern::Mask(mask) => board.completed_line(mask, cell_index),
+        }
+    }
+
+    pub fn completed_anywhere(&self, board: &Board) -> Option<CellMask> {
+        let (width, height) = (board.numbers.width(), board.numbers.height());
+        match self {
+            WinPattern::Row => (0..height)
+                .find_map(|y| board.completed_shifted_line(&board.first_row_mask, board.numbers.index_of(0, y))),
+            WinPattern::Column => (0..width)
+                .find_map(|x| board.completed_shifted_line(&board.first_column_mask, x)),
+            WinPattern::Diagonal => board.completed_line_anywhere(&board.main_diagonal_mask)
+                .or_else(|| board.completed_line_anywhere(&board.anti_diagonal_mask)),
+            WinPattern::FourCorners => board.completed_line_anywhere(&board.corners_mask),
+            WinPattern::X => board.completed_line_anywhere(&board.main_diagonal_mask.union(&board.anti_diagonal_mask)),
+            WinPattern::Blackout => board.completed_line_anywhere(&board.all_cells_mask),
+            WinPattern::Mask(mask) => board.completed_line_anywhere(mask),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            WinPattern::Row => "row",
+            WinPattern::Column => "column",
+            WinPattern::Diagonal => "diagonal",
+            WinPattern::FourCorners => "four corners",
+            WinPattern::X => "X",
+            WinPattern::Blackout => "blackout",
+            WinPattern::Mask(_) => "mask",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cell {
+    number: Option<u32>,
     is_marked: bool,
 }
 
+impl FromStr for Cell {
+    type Err = std::num::ParseIntError;
+
+    fn from_str(token: &str) -> Result<Self, Self::Err> {
+        if token == "*" || token == "FREE" {
+            Ok(Cell::free())
+        } else if let Some(digits) = token.strip_suffix('*') {
+            Ok(Cell::new(digits.parse()?, true))
+        } else {
+            Ok(Cell::new(token.parse()?, false))
+        }
+    }
+}
+
+// Written the way FromStr reads it.
+impl fmt::Display for Cell {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.number, self.is_marked) {
+            (None, _) => write!(f, "*"),
+            (Some(number), true) => write!(f, "{}*", number),
+            (Some(number), false) => write!(f, "{}", number),
+        }
+    }
+}
+
 impl Cell {
     pub fn new(number: u32, is_marked: bool) -> Self {
-        Cell { number, is_marked }
+        Cell { number: Some(number), is_marked }
+    }
+
+    pub fn free() -> Self {
+        Cell { number: None, is_marked: true }
+    }
+
+    pub fn number(&self) -> Option<u32> {
+        self.number
     }
 }
 
@@ -171,7 +582,13 @@ mod tests {
 
     #[test]
     fn parse_example_numbers() {
-        assert_eq!(parse_numbers_csv("7,4,9,5,11"), vec![7, 4, 9, 5, 11]);
+        assert_eq!(parse_numbers_csv("7,4,9,5,11", 1).unwrap(), vec![7, 4, 9, 5, 11]);
+    }
+
+    #[test]
+    fn parse_numbers_csv_reports_bad_token() {
+        assert_eq!(parse_numbers_csv("7,4,x,5", 3).unwrap_err().to_string(),
+                   "line 3, column 5: can't parse \"x\" as u32");
     }
 
     #[test]
@@ -197,6 +614,164 @@ mod tests {
         assert_eq!(read_boards(input), expected);
     }
 
+    #[test]
+    fn read_boards_of_other_sizes() {
+        let input = to_string_iter(vec![
+            "",
+            "1 2 3",
+            "4 5 6",
+            "7 8 9",
+            "",
+            "10 11 12 13",
+            "14 15 16 17",
+        ]);
+        let boards = read_boards(input);
+        assert_eq!(boards, vec![
+            Board::with_size(3, 3, &(1..=9).collect_vec()),
+            Board::with_size(4, 2, &(10..=17).collect_vec()),
+        ]);
+    }
+
+    #[test]
+    fn read_boards_ignores_extra_blank_lines() {
+        let input = to_string_iter(vec![
+            "",
+            "1 2",
+            "3 4",
+            "",
+            "",
+            "5 6",
+            "7 8",
+            "",
+        ]);
+        assert_eq!(read_boards(input).len(), 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "line 4, column 4: row has 2 values, expected 3")]
+    fn read_board_rejects_ragged_rows() {
+        let input = to_string_iter(vec![
+            "",
+            "1 2 3",
+            "4 5",
+        ]);
+        read_boards(input);
+    }
+
+    #[test]
+    fn parse_free_and_pre_marked_cells() {
+        assert_eq!("*".parse::<Cell>(), Ok(Cell::free()));
+        assert_eq!("FREE".parse::<Cell>(), Ok(Cell::free()));
+        assert_eq!("12*".parse::<Cell>(), Ok(Cell::new(12, true)));
+        assert_eq!("12".parse::<Cell>(), Ok(Cell::new(12, false)));
+        assert!("x*".parse::<Cell>().is_err());
+        for token in ["*", "7", "7*"] {
+            assert_eq!(token.parse::<Cell>().unwrap().to_string(), token);
+        }
+    }
+
+    #[test]
+    fn try_read_boards_reports_bad_cell() {
+        let input = to_string_iter(vec![
+            "1 2",
+            "3 x",
+        ]);
+        assert_eq!(try_read_boards(input, 2).unwrap_err().to_string(),
+                   "line 3, column 3: can't parse \"x\" as Cell");
+    }
+
+    #[test]
+    fn boards_over_128_cells_win_across_mask_words() {
+        // Row 10 of a 12x12 board holds cells 120 to 131, either side of the first word.
+        let read_big_board = || {
+            let input = to_string_iter(vec![""])
+                .chain((0..12).map(|y| (0..12).map(|x| (y * 12 + x).to_string()).join(" ")));
+            try_read_boards(input, 2).unwrap()
+        };
+        let mut boards = read_big_board();
+        let row = (120..132).collect_vec();
+        assert_eq!(play_to_first_winning_board(&mut boards, &row, &[WinPattern::Row]), Some((0, 131)));
+        assert_eq!(boards[0].winning_line(), Some((120..132).collect_vec()));
+
+        let mut boards = read_big_board();
+        let column = (0..12).map(|y| y * 12 + 11).collect_vec();
+        assert_eq!(play_to_first_winning_board(&mut boards, &column, &[WinPattern::Column]), Some((0, 143)));
+        assert_eq!(boards[0].winning_line(), Some(column.iter().map(|&number| number as usize).collect_vec()));
+    }
+
+    #[test]
+    fn read_board_with_free_centre() {
+        let input = to_string_iter(vec![
+            "",
+            "1  2 3",
+            "4 FREE 6",
+            "7  8* 9",
+        ]);
+        let boards = read_boards(input);
+        assert_eq!(boards[0].cell(4), Cell::free());
+        assert_eq!(boards[0].cell(7), Cell::new(8, true));
+        assert_eq!(boards[0].sum_unmarked_numbers(), 1 + 2 + 3 + 4 + 6 + 7 + 9);
+    }
+
+    #[test]
+    fn free_cells_are_not_indexed() {
+        let boards = vec![Board::from_cells(2, 1, vec![Cell::free(), Cell::new(5, false)])];
+        let cell_indexes = build_cell_indexes(&boards);
+        assert_eq!(cell_indexes.len(), 1);
+        assert_eq!(*cell_indexes.get(&5).unwrap(), vec![(0, 1)]);
+    }
+
+    #[test]
+    fn free_centre_counts_toward_row_and_column_wins() {
+        let input = to_string_iter(vec![
+            "1,2,4,8",
+            "",
+            "1  2 3",
+            "4  * 6",
+            "7  8 9",
+        ]);
+        let unmarked_sum = 3 + 6 + 7 + 9;
+        assert_eq!(day4a(input), ("day4a", unmarked_sum * 8));
+    }
+
+    #[test]
+    fn pre_marked_cells_count_toward_wins_but_not_score() {
+        let mut boards = vec![Board::from_cells(3, 1, vec![
+            Cell::new(1, true), Cell::new(2, false), Cell::new(3, true),
+        ])];
+        assert_eq!(play_to_first_winning_board(&mut boards, &[2], ROWS_AND_COLUMNS), Some((0, 2)));
+        assert_eq!(boards[0].sum_unmarked_numbers(), 0);
+    }
+
+    #[test]
+    fn fully_pre_marked_row_wins_on_the_first_draw() {
+        let mut boards = vec![
+            Board::with_size(2, 2, &[5, 6, 7, 8]),
+            Board::from_cells(2, 2, vec![
+                Cell::new(3, false), Cell::new(4, false),
+                Cell::new(1, true), Cell::free(),
+            ]),
+        ];
+        let results = play_all_boards(&mut boards, &[9, 5, 6], ROWS_AND_COLUMNS);
+        assert_eq!(results.first_winner().unwrap().board_index, 1);
+        assert_eq!(results.board(1).win, Some(BoardWin { draw_index: 0, number: 9, score: 63 }));
+        assert_eq!(boards[1].winning_pattern(), Some(&WinPattern::Row));
+        assert_eq!(boards[1].winning_line(), Some(vec![2, 3]));
+        assert_eq!(results.board(0).win.map(|win| win.draw_index), Some(2));
+    }
+
+    #[test]
+    fn pre_marked_patterns_win_even_when_the_first_draw_is_on_the_board() {
+        let mut boards = vec![Board::from_cells(3, 3, vec![
+            Cell::new(1, true), Cell::new(2, false), Cell::new(3, false),
+            Cell::new(4, false), Cell::free(), Cell::new(6, false),
+            Cell::new(7, false), Cell::new(8, false), Cell::new(9, true),
+        ])];
+        assert_eq!(play_to_first_winning_board(&mut boards, &[2, 3], &[WinPattern::Diagonal]), Some((0, 2)));
+        assert_eq!(boards[0].winning_line(), Some(vec![0, 4, 8]));
+        assert!(boards[0].is_marked(1));
+    }
+
     #[test]
     fn build_example_cell_indexes() {
         let boards = vec![
@@ -213,10 +788,10 @@ mod tests {
     #[test]
     fn board_wins_only_once() {
         let mut board = Board::new(&(1..=25).collect_vec());
-        (0..=3).for_each(|cell_index| assert!(!board.play_cell(cell_index)));
-        (5..=8).for_each(|cell_index| assert!(!board.play_cell(cell_index)));
-        assert!(board.play_cell(4));
-        assert!(!board.play_cell(9));
+        (0..=3).for_each(|cell_index| assert!(board.play_cell(cell_index, ROWS_AND_COLUMNS).is_none()));
+        (5..=8).for_each(|cell_index| assert!(board.play_cell(cell_index, ROWS_AND_COLUMNS).is_none()));
+        assert!(board.play_cell(4, ROWS_AND_COLUMNS).is_some());
+        assert!(board.play_cell(9, ROWS_AND_COLUMNS).is_none());
     }
 
     #[test]
@@ -237,18 +812,144 @@ mod tests {
         assert!(board.is_cell_in_fully_marked_column(11));
     }
 
+    #[test]
+    fn rectangular_board_wins_on_row_and_column() {
+        let mut board = Board::with_size(4, 2, &(1..=8).collect_vec());
+        (4..=6).for_each(|cell_index| assert!(board.play_cell(cell_index, ROWS_AND_COLUMNS).is_none()));
+        assert!(board.play_cell(7, ROWS_AND_COLUMNS).is_some());
+
+        let mut board = Board::with_size(4, 2, &(1..=8).collect_vec());
+        assert!(board.play_cell(2, ROWS_AND_COLUMNS).is_none());
+        assert!(board.play_cell(6, ROWS_AND_COLUMNS).is_some());
+    }
+
+    #[test]
+    fn seven_by_seven_board_wins_on_column() {
+        let mut board = Board::with_size(7, 7, &(1..=49).collect_vec());
+        (0..6).for_each(|row| assert!(board.play_cell(row * 7 + 3, ROWS_AND_COLUMNS).is_none()));
+        assert!(board.play_cell(6 * 7 + 3, ROWS_AND_COLUMNS).is_some());
+    }
+
+    #[test]
+    fn nine_by_eight_board_wins_on_last_row_and_corners() {
+        let input = (0..8).map(|row| (1..=9).map(|col| (row * 9 + col).to_string()).join(" ")).collect_vec();
+        let mut boards = read_boards(input.into_iter());
+        assert_eq!(boards[0].num_cells(), 72);
+        (63..71).for_each(|cell_index| assert!(boards[0].play_cell(cell_index, ROWS_AND_COLUMNS).is_none()));
+        assert_eq!(boards[0].play_cell(71, ROWS_AND_COLUMNS), Some(WinPattern::Row));
+        assert_eq!(boards[0].winning_line(), Some((63..72).collect_vec()));
+
+        let mut board = Board::with_size(9, 8, &(1..=72).collect_vec());
+        [0, 8, 63].into_iter().for_each(|cell_index| assert!(board.play_cell(cell_index, &[WinPattern::FourCorners]).is_none()));
+        assert_eq!(board.play_cell(71, &[WinPattern::FourCorners, WinPattern::Diagonal]), Some(WinPattern::FourCorners));
+    }
+
+    #[test]
+    fn board_reports_winning_pattern() {
+        let mut board = Board::new(&(1..=25).collect_vec());
+        let patterns = [WinPattern::Row, WinPattern::Diagonal];
+        vec![0, 6, 12, 18].into_iter()
+            .for_each(|cell_index| assert_eq!(board.play_cell(cell_index, &patterns), None));
+        assert_eq!(board.play_cell(24, &patterns), Some(WinPattern::Diagonal));
+        assert_eq!(board.winning_pattern(), Some(&WinPattern::Diagonal));
+    }
+
+    #[test]
+    fn board_wins_on_anti_diagonal() {
+        let mut board = Board::with_size(3, 3, &(1..=9).collect_vec());
+        assert_eq!(board.play_cell(2, &[WinPattern::Diagonal]), None);
+        assert_eq!(board.play_cell(6, &[WinPattern::Diagonal]), None);
+        assert_eq!(board.play_cell(4, &[WinPattern::Diagonal]), Some(WinPattern::Diagonal));
+    }
+
+    #[test]
+    fn rectangular_board_never_wins_on_diagonal() {
+        let mut board = Board::with_size(3, 2, &(1..=6).collect_vec());
+        (0..6).for_each(|cell_index| assert_eq!(board.play_cell(cell_index, &[WinPattern::Diagonal, WinPattern::X]), None));
+    }
+
+    #[test]
+    fn board_wins_on_four_corners() {
+        let mut board = Board::new(&(1..=25).collect_vec());
+        let patterns = [WinPattern::FourCorners];
+        vec![0, 4, 20].into_iter()
+            .for_each(|cell_index| assert_eq!(board.play_cell(cell_index, &patterns), None));
+        assert_eq!(board.play_cell(24, &patterns), Some(WinPattern::FourCorners));
+    }
+
+    #[test]
+    fn board_wins_on_x_only_when_both_diagonals_marked() {
+        let mut board = Board::with_size(3, 3, &(1..=9).collect_vec());
+        let patterns = [WinPattern::X];
+        vec![0, 4, 8, 2].into_iter()
+            .for_each(|cell_index| assert_eq!(board.play_cell(cell_index, &patterns), None));
+        assert_eq!(board.play_cell(6, &patterns), Some(WinPattern::X));
+    }
+
+    #[test]
+    fn board_wins_on_blackout() {
+        let mut board = Board::with_size(2, 2, &(1..=4).collect_vec());
+        (0..3).for_each(|cell_index| assert_eq!(board.play_cell(cell_index, &[WinPattern::Blackout]), None));
+        assert_eq!(board.play_cell(3, &[WinPattern::Blackout]), Some(WinPattern::Blackout));
+    }
+
+    #[test]
+    fn board_wins_on_mask() {
+        let mask = WinPattern::mask(&[false, true, false, true, false, true, false, true, false]);
+        let patterns = [mask.clone()];
+        let mut board = Board::with_size(3, 3, &(1..=9).collect_vec());
+        vec![1, 3, 5, 0].into_iter()
+            .for_each(|cell_index| assert_eq!(board.play_cell(cell_index, &patterns), None));
+        assert_eq!(board.play_cell(7, &patterns), Some(mask));
+    }
+
+    #[test]
+    fn first_pattern_in_set_is_reported() {
+        let mut board = Board::with_size(2, 2, &(1..=4).collect_vec());
+        board.mark_cell(0);
+        board.mark_cell(1);
+        board.mark_cell(2);
+        assert_eq!(board.play_cell(3, &[WinPattern::Blackout, WinPattern::Row]), Some(WinPattern::Blackout));
+    }
+
+    #[test]
+    fn play_boards_to_diagonal_win() {
+        let mut boards = vec![Board::new(&(1..=25).collect_vec())];
+        let numbers = vec![1, 2, 7, 13, 19, 25, 3];
+        let patterns = [WinPattern::Row, WinPattern::Column, WinPattern::Diagonal];
+        assert_eq!(play_to_first_winning_board(&mut boards, &numbers, &patterns), Some((0, 25)));
+        assert_eq!(boards[0].winning_pattern(), Some(&WinPattern::Diagonal));
+    }
+
+    #[test]
+    fn board_records_winning_line() {
+        let mut board = Board::with_size(3, 3, &(1..=9).collect_vec());
+        vec![3, 5].into_iter().for_each(|cell_index| assert_eq!(board.play_cell(cell_index, ROWS_AND_COLUMNS), None));
+        assert_eq!(board.winning_line(), None);
+        assert_eq!(board.play_cell(4, ROWS_AND_COLUMNS), Some(WinPattern::Row));
+        assert_eq!(board.winning_line(), Some(vec![3, 4, 5]));
+    }
+
+    #[test]
+    fn x_winning_line_includes_centre_once() {
+        let mut board = Board::with_size(3, 3, &(1..=9).collect_vec());
+        vec![0, 2, 4, 6].into_iter().for_each(|cell_index| board.mark_cell(cell_index));
+        assert_eq!(board.play_cell(8, &[WinPattern::X]), Some(WinPattern::X));
+        assert_eq!(board.winning_line(), Some(vec![0, 2, 4, 6, 8]));
+    }
+
     #[test]
     fn play_missing_number() {
         let mut boards = vec![Board::new(&(1..=25).collect_vec())];
         let numbers = vec![26];
-        assert_eq!(play_to_first_winning_board(&mut boards, &numbers), None);
+        assert_eq!(play_to_first_winning_board(&mut boards, &numbers, ROWS_AND_COLUMNS), None);
     }
 
     #[test]
     fn play_example_board_with_row_win() {
         let mut boards = vec![Board::new(&(11..=35).collect_vec())];
         let numbers = vec![1, 16, 2, 18, 20, 19, 17, 22];
-        if let Some((winning_board_index, winning_number)) = play_to_first_winning_board(&mut boards, &numbers) {
+        if let Some((winning_board_index, winning_number)) = play_to_first_winning_board(&mut boards, &numbers, ROWS_AND_COLUMNS) {
             assert_eq!(winning_number, 17);
             assert_eq!(boards[winning_board_index].sum_unmarked_numbers(),
                        (11..=35).sum::<u32>() - (16..=20).sum::<u32>());
@@ -261,15 +962,23 @@ mod tests {
     fn play_example_board_with_column_win() {
         let mut boards = vec![Board::new(&(11..=35).collect_vec())];
         let numbers = vec![1, 12, 2, 17, 32, 27, 22, 34];
-        if let Some((winning_board_index, winning_number)) = play_to_first_winning_board(&mut boards, &numbers) {
+        if let Some((winning_board_index, winning_number)) = play_to_first_winning_board(&mut boards, &numbers, ROWS_AND_COLUMNS) {
             assert_eq!(winning_number, 22);
             assert_eq!(boards[winning_board_index].sum_unmarked_numbers(),
-                       (11..=35).sum::<u32>() - vec![12, 17, 22, 27, 32].iter().sum::<u32>());
+                       (11..=35).sum::<u32>() - [12, 17, 22, 27, 32].iter().sum::<u32>());
         } else {
             panic!("No winning board");
         }
     }
 
+    #[test]
+    fn count_marked_cells() {
+        let mut board = Board::from_cells(2, 1, vec![Cell::free(), Cell::new(5, false)]);
+        assert_eq!(board.num_marked(), 1);
+        board.mark_cell(1);
+        assert_eq!(board.num_marked(), 2);
+    }
+
     #[test]
     fn score_example_board() {
         let mut board = Board::new(&(11..=35).rev().collect_vec());
@@ -347,4 +1056,33 @@ mod tests {
         ]);
         assert_eq!(day4b(input), ("day4b", 1924));
     }
+
+    #[test]
+    fn day4b_ties_on_the_last_draw_go_to_the_first_board() {
+        let input = to_string_iter(vec![
+            "1,2",
+            "",
+            "1 9",
+            "2 9",
+            "",
+            "1 8",
+            "2 8",
+        ]);
+        assert_eq!(day4b(input), ("day4b", 36));
+    }
+
+    #[test]
+    fn play_to_last_winning_board_needs_every_board_to_win() {
+        let mut boards = read_boards(to_string_iter(vec!["", "1 2", "", "3 4"]));
+        assert_eq!(play_to_last_winning_board(&mut boards, &[1, 3], &[WinPattern::Column]), Some((1, 3)));
+        let mut boards = read_boards(to_string_iter(vec!["", "1 2", "", "3 4"]));
+        assert_eq!(play_to_last_winning_board(&mut boards, &[1, 3], &[WinPattern::Row]), None);
+    }
+
+    #[test]
+    fn play_to_first_winning_board_stops_at_the_first_win() {
+        let mut boards = read_boards(to_string_iter(vec!["", "1 2", "", "3 4"]));
+        assert_eq!(play_to_first_winning_board(&mut boards, &[1, 2, 3], &[WinPattern::Column]), Some((0, 1)));
+        assert_eq!(boards[1].sum_unmarked_numbers(), 7);
+    }
 }
\ No newline at end of file
@@ -0,0 +1,384 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::process::{Command, Stdio};
+use itertools::Itertools;
+use crate::day4::{Board, build_cell_indexes, claim_completed_boards, parse_numbers_csv, play_number_on_all_boards, read_boards, ROWS_AND_COLUMNS};
+
+const SAVE_HEADER: &str = "drawn:";
+const MARKED: &str = "\x1b[7m";
+const WINNING: &str = "\x1b[1;42m";
+const RESET: &str = "\x1b[0m";
+
+// Reads a command a line at a time, or with keypresses each key as it's pressed, if the
+// terminal can be switched over; otherwise it says so and falls back to lines.
+#[allow(dead_code)]
+pub fn day4_caller(input: impl Iterator<Item=String>, keypresses: bool) -> io::Result<()> {
+    let lines = input.collect_vec();
+    let mut caller = if lines.first().is_some_and(|line| line.starts_with(SAVE_HEADER)) {
+        BingoCaller::from_save(lines)
+    } else {
+        BingoCaller::from_input(lines)
+    };
+    if keypresses {
+        match KeypressMode::enable() {
+            Ok(_keypress_mode) => return caller.run_keypresses(io::stdin().lock(), io::stdout()),
+            Err(error) => eprintln!("Reading a line at a time, as keypresses aren't available: {}", error),
+        }
+    }
+    caller.run(io::stdin().lock(), io::stdout())
+}
+
+// Switches the terminal to cbreak mode, so that each keypress reaches the caller without
+// waiting for Enter, and restores the previous settings when dropped.
+struct KeypressMode {
+    saved_settings: String,
+}
+
+impl KeypressMode {
+    fn enable() -> io::Result<Self> {
+        if !io::stdin().is_terminal() {
+            return Err(io::Error::other("stdin is not a terminal"));
+        }
+        let saved_settings = stty(&["-g"])?;
+        stty(&["-icanon", "min", "1"])?;
+        Ok(KeypressMode { saved_settings })
+    }
+}
+
+impl Drop for KeypressMode {
+    fn drop(&mut self) {
+        if let Err(error) = stty(&[&self.saved_settings]) {
+            eprintln!("Couldn't restore the terminal settings: {}", error);
+        }
+    }
+}
+
+fn stty(args: &[&str]) -> io::Result<String> {
+    let output = Command::new("stty").args(args).stdin(Stdio::inherit()).output()?;
+    if !output.status.success() {
+        return Err(io::Error::other(format!("stty {} failed: {}", args.join(" "),
+                                            String::from_utf8_lossy(&output.stderr).trim())));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+enum CallerCommand {
+    Draw,
+    Save(String),
+    Quit,
+    Unknown(String),
+}
+
+pub struct BingoCaller {
+    input_lines: Vec<String>,
+    numbers: Vec<u32>,
+    boards: Vec<Board>,
+    cell_indexes: HashMap<u32, Vec<(usize, usize)>>,
+    num_drawn: usize,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Announcement {
+    pub board_index: usize,
+    pub pattern_name: &'static str,
+    pub line_numbers: Vec<u32>,
+    pub score: u32,
+}
+
+#[allow(dead_code)]
+impl BingoCaller {
+    pub fn from_input(input_lines: Vec<String>) -> Self {
+        let numbers = parse_numbers_csv(&input_lines[0], 1).unwrap_or_else(|error| panic!("{}", error));
+        let boards = read_boards(input_lines[1..].iter().cloned());
+        let cell_indexes = build_cell_indexes(&boards);
+        BingoCaller { input_lines, numbers, boards, cell_indexes, num_drawn: 0 }
+    }
+
+    pub fn from_save(save_lines: Vec<String>) -> Self {
+        let num_drawn: usize = save_lines[0].strip_prefix(SAVE_HEADER)
+            .and_then(|count| count.trim().parse().ok())
+            .unwrap_or_else(|| panic!("Bad save header {:?}", save_lines[0]));
+        let mut caller = BingoCaller::from_input(save_lines[1..].to_vec());
+        for _draw in 0..num_drawn {
+            caller.draw_next();
+        }
+        caller
+    }
+
+    pub fn save(&self) -> Vec<String> {
+        let mut lines = vec![format!("{}{}", SAVE_HEADER, self.num_drawn)];
+        lines.extend(self.input_lines.iter().cloned());
+        lines
+    }
+
+    pub fn num_drawn(&self) -> usize {
+        self.num_drawn
+    }
+
+    pub fn draw_next(&mut self) -> Option<(u32, Vec<Announcement>)> {
+        let number = *self.numbers.get(self.num_drawn)?;
+        self.num_drawn += 1;
+        let mut winners = play_number_on_all_boards(number, &mut self.boards, &self.cell_indexes, ROWS_AND_COLUMNS);
+        if self.num_drawn == 1 {
+            winners.extend(claim_completed_boards(&mut self.boards, ROWS_AND_COLUMNS));
+            winners.sort_unstable();
+        }
+        let announcements = winners.into_iter()
+            .map(|board_index| self.announce(board_index, number))
+            .collect_vec();
+        Some((number, announcements))
+    }
+
+    fn announce(&self, board_index: usize, number: u32) -> Announcement {
+        let board = &self.boards[board_index];
+        let numbers = board.numbers().collect_vec();
+        Announcement {
+            board_index,
+            pattern_name: board.winning_pattern().unwrap().name(),
+            line_numbers: board.winning_line().unwrap().into_iter()
+                .filter_map(|cell_index| numbers[cell_index])
+                .collect_vec(),
+            score: board.sum_unmarked_numbers() * number,
+        }
+    }
+
+    pub fn render(&self) -> String {
+        let number_width = self.numbers.iter().max().map_or(1, |max| max.to_string().len());
+        self.boards.iter().enumerate()
+            .map(|(board_index, board)| render_board(board_index, board, number_width))
+            .join("\n")
+    }
+
+    // One command per line: an empty line or a space draws, q quits, and s FILE saves.
+    pub fn run(&mut self, reader: impl BufRead, mut writer: impl Write) -> io::Result<()> {
+        writeln!(writer, "Enter: draw a number, s FILE: save, q: quit")?;
+        for line in reader.lines() {
+            let line = line?;
+            let command = match line.trim() {
+                "" => CallerCommand::Draw,
+                "q" => CallerCommand::Quit,
+                command => match command.strip_prefix("s ") {
+                    Some(path) => CallerCommand::Save(path.to_string()),
+                    None => CallerCommand::Unknown(format!("command {:?}", command)),
+                },
+            };
+            if !self.command(command, &mut writer)? {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    // Space or Enter draws, q quits, and s saves to the path typed after it up to Enter.
+    pub fn run_keypresses(&mut self, reader: impl BufRead, mut writer: impl Write) -> io::Result<()> {
+        writeln!(writer, "Space or Enter: draw a number, s FILE: save, q: quit")?;
+        let mut keys = reader.bytes();
+        while let Some(key) = keys.next() {
+            let command = match key? {
+                b' ' | b'\n' | b'\r' => CallerCommand::Draw,
+                b'q' => CallerCommand::Quit,
+                b's' => {
+                    let mut path = vec![];
+                    for key in keys.by_ref() {
+                        match key? {
+                            b'\n' => break,
+                            key => path.push(key),
+                        }
+                    }
+                    CallerCommand::Save(String::from_utf8_lossy(&path).to_string())
+                }
+                other => CallerCommand::Unknown(format!("key {:?}", other as char)),
+            };
+            if !self.command(command, &mut writer)? {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    // False once the caller should stop.
+    fn command(&mut self, command: CallerCommand, writer: &mut impl Write) -> io::Result<bool> {
+        match command {
+            CallerCommand::Draw => match self.draw_next() {
+                Some((number, announcements)) => {
+                    writeln!(writer, "Draw {}: {}", self.num_drawn, number)?;
+                    writeln!(writer, "{}", self.render())?;
+                    for announcement in announcements {
+                        writeln!(writer, "BINGO! Board {} wins with {} ({}), score {}",
+                                 announcement.board_index + 1, with_article(announcement.pattern_name),
+                                 announcement.line_numbers.iter().join(" "), announcement.score)?;
+                    }
+                }
+                None => writeln!(writer, "No numbers left to draw")?,
+            },
+            CallerCommand::Quit => return Ok(false),
+            CallerCommand::Save(path) => {
+                let path = path.trim();
+                // A bad path shouldn't end the game, so the error is reported and play goes on.
+                match fs::write(path, self.save().join("\n") + "\n") {
+                    Ok(()) => writeln!(writer, "Saved after {} draws to {}", self.num_drawn, path)?,
+                    Err(error) => writeln!(writer, "Couldn't save to {}: {}", path, error)?,
+                }
+            }
+            CallerCommand::Unknown(what) => writeln!(writer, "Unknown {}", what)?,
+        }
+        writer.flush()?;
+        Ok(true)
+    }
+}
+
+fn with_article(pattern_name: &str) -> String {
+    match pattern_name {
+        "four corners" => pattern_name.to_string(),
+        "X" => format!("an {}", pattern_name),
+        _ => format!("a {}", pattern_name),
+    }
+}
+
+fn render_board(board_index: usize, board: &Board, number_width: usize) -> String {
+    let winning_line = board.winning_line().unwrap_or_default();
+    let title = if board.has_won() {
+        format!("Board {} BINGO", board_index + 1)
+    } else {
+        format!("Board {}", board_index + 1)
+    };
+    let grid = board.number_grid();
+    let rows = (0..grid.height())
+        .map(|y| grid.row_indexes(y).zip(grid.row(y))
+            .map(|(cell_index, number)| {
+                let text = match number {
+                    Some(number) => format!("{:>width$}", number, width = number_width),
+                    None => format!("{:>width$}", "*", width = number_width),
+                };
+                if winning_line.contains(&cell_index) {
+                    format!("{}{}{}", WINNING, text, RESET)
+                } else if board.is_marked(cell_index) {
+                    format!("{}{}{}", MARKED, text, RESET)
+                } else {
+                    text
+                }
+            })
+            .join(" "))
+        .join("\n");
+    format!("{}\n{}\n", title, rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use crate::day4caller::*;
+    use crate::util::to_string_iter;
+
+    fn small_input() -> Vec<String> {
+        to_string_iter(vec![
+            "3,1,2,4",
+            "",
+            "1 2",
+            "3 4",
+            "",
+            "4 *",
+            "3 1",
+        ]).collect_vec()
+    }
+
+    #[test]
+    fn draw_announces_winners_with_line_and_score() {
+        let mut caller = BingoCaller::from_input(small_input());
+        assert_eq!(caller.draw_next(), Some((3, vec![])));
+        assert_eq!(caller.draw_next(), Some((1, vec![
+            Announcement { board_index: 0, pattern_name: "column", line_numbers: vec![1, 3], score: 6 },
+            Announcement { board_index: 1, pattern_name: "row", line_numbers: vec![3, 1], score: 4 },
+        ])));
+        caller.draw_next();
+        caller.draw_next();
+        assert_eq!(caller.draw_next(), None);
+    }
+
+    #[test]
+    fn render_highlights_marked_cells() {
+        let mut caller = BingoCaller::from_input(small_input());
+        caller.draw_next();
+        assert_eq!(caller.render(),
+                   "Board 1\n1 2\n\x1b[7m3\x1b[0m 4\n\n\
+                    Board 2\n4 \x1b[7m*\x1b[0m\n\x1b[7m3\x1b[0m 1\n");
+    }
+
+    #[test]
+    fn render_highlights_winning_lines() {
+        let mut caller = BingoCaller::from_input(small_input());
+        caller.draw_next();
+        caller.draw_next();
+        assert_eq!(caller.render(),
+                   "Board 1 BINGO\n\x1b[1;42m1\x1b[0m 2\n\x1b[1;42m3\x1b[0m 4\n\n\
+                    Board 2 BINGO\n4 \x1b[7m*\x1b[0m\n\x1b[1;42m3\x1b[0m \x1b[1;42m1\x1b[0m\n");
+    }
+
+    #[test]
+    fn save_and_resume_reproduces_board_states() {
+        let mut caller = BingoCaller::from_input(small_input());
+        caller.draw_next();
+        caller.draw_next();
+        let saved = caller.save();
+        assert_eq!(saved[0], "drawn:2");
+
+        let resumed = BingoCaller::from_save(saved);
+        assert_eq!(resumed.num_drawn(), 2);
+        assert_eq!(resumed.boards, caller.boards);
+    }
+
+    #[test]
+    fn run_keypresses_draws_on_space_or_enter_until_quit() {
+        let mut caller = BingoCaller::from_input(small_input());
+        let mut output = vec![];
+        caller.run_keypresses(Cursor::new(" \nxq "), &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("Draw 1: 3\n"));
+        assert!(output.contains("Draw 2: 1\n"));
+        assert!(output.contains("BINGO! Board 1 wins with a column (1 3), score 6\n"));
+        assert!(output.contains("BINGO! Board 2 wins with a row (3 1), score 4\n"));
+        assert!(output.contains("Unknown key 'x'\n"));
+        assert!(!output.contains("Draw 3"));
+        assert_eq!(caller.num_drawn(), 2);
+    }
+
+    #[test]
+    fn run_keypresses_reports_when_numbers_run_out() {
+        let mut caller = BingoCaller::from_input(small_input());
+        let mut output = vec![];
+        caller.run_keypresses(Cursor::new("\n\n\n\n\n"), &mut output).unwrap();
+        assert!(String::from_utf8(output).unwrap().ends_with("No numbers left to draw\n"));
+    }
+
+    #[test]
+    fn run_keypresses_keeps_playing_when_save_fails() {
+        let mut caller = BingoCaller::from_input(small_input());
+        let mut output = vec![];
+        caller.run_keypresses(Cursor::new("s /no/such/dir/game.txt\n q"), &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("Couldn't save to /no/such/dir/game.txt: "));
+        assert!(output.contains("Draw 1: 3\n"));
+    }
+
+    #[test]
+    fn run_reads_a_command_per_line() {
+        let mut caller = BingoCaller::from_input(small_input());
+        let mut output = vec![];
+        caller.run(Cursor::new("\n \r\nx\ns /no/such/dir/game.txt\nq\n\n"), &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("Draw 1: 3\n"));
+        assert!(output.contains("Draw 2: 1\n"));
+        assert!(output.contains("BINGO! Board 2 wins with a row (3 1), score 4\n"));
+        assert!(output.contains("Unknown command \"x\"\n"));
+        assert!(output.contains("Couldn't save to /no/such/dir/game.txt: "));
+        assert!(!output.contains("Draw 3"));
+        assert_eq!(caller.num_drawn(), 2);
+    }
+
+    #[test]
+    fn pattern_names_take_the_right_article() {
+        assert_eq!(with_article("row"), "a row");
+        assert_eq!(with_article("X"), "an X");
+        assert_eq!(with_article("four corners"), "four corners");
+    }
+}
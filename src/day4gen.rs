@@ -0,0 +1,207 @@
+use std::collections::HashMap;
+use std::ops::RangeInclusive;
+use itertools::Itertools;
+use crate::util::Rng;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BingoParams {
+    pub num_boards: usize,
+    pub width: usize,
+    pub height: usize,
+    pub number_range: RangeInclusive<u32>,
+    pub unique_within_board: bool,
+    pub guaranteed_win: Option<GuaranteedWin>,
+    pub seed: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GuaranteedWin {
+    pub board_index: usize,
+    pub draw_index: usize,
+}
+
+#[allow(dead_code)]
+impl BingoParams {
+    pub fn new(num_boards: usize, seed: u64) -> Self {
+        BingoParams {
+            num_boards,
+            width: 5,
+            height: 5,
+            number_range: 0..=99,
+            unique_within_board: true,
+            guaranteed_win: None,
+            seed,
+        }
+    }
+}
+
+#[allow(dead_code)]
+pub fn generate_bingo_input(params: &BingoParams) -> Vec<String> {
+    let num_cells = params.width * params.height;
+    let pool_size = params.number_range.clone().count();
+    assert!(num_cells > 0, "Boards must have at least one cell");
+    assert!(!params.unique_within_board || num_cells <= pool_size,
+            "Cannot place {} unique numbers from a range of {}", num_cells, pool_size);
+
+    let mut rng = Rng::new(params.seed);
+    let candidates = params.number_range.clone().collect_vec();
+    let mut draws = candidates.clone();
+    rng.shuffle(&mut draws);
+
+    let number_width = params.number_range.end().to_string().len();
+    let mut lines = vec![draws.iter().join(",")];
+    for board_index in 0..params.num_boards {
+        let numbers = match params.guaranteed_win {
+            Some(win) if win.board_index == board_index =>
+                winning_board_numbers(params, &draws, win.draw_index, &mut rng),
+            _ => random_board_numbers(params, &candidates, num_cells, &mut rng),
+        };
+        lines.push(String::new());
+        lines.extend(numbers.chunks(params.width)
+            .map(|row| row.iter().map(|num| format!("{:>width$}", num, width = number_width)).join(" ")));
+    }
+    lines
+}
+
+fn random_board_numbers(params: &BingoParams, candidates: &[u32], count: usize, rng: &mut Rng) -> Vec<u32> {
+    if params.unique_within_board {
+        pick_unique(candidates, count, rng)
+    } else {
+        (0..count)
+            .map(|_| candidates[rng.below(candidates.len() as u64) as usize])
+            .collect_vec()
+    }
+}
+
+// The first count steps of a shuffle, keeping only the moved candidates, so that a few
+// numbers from a large range don't cost a copy of the whole range.
+fn pick_unique(candidates: &[u32], count: usize, rng: &mut Rng) -> Vec<u32> {
+    let mut moved = HashMap::new();
+    (0..count)
+        .map(|index| {
+            let swap_index = index + rng.below((candidates.len() - index) as u64) as usize;
+            let picked = moved.get(&swap_index).copied().unwrap_or(candidates[swap_index]);
+            moved.insert(swap_index, moved.get(&index).copied().unwrap_or(candidates[index]));
+            picked
+        })
+        .collect_vec()
+}
+
+// One row is built from draws up to and including the winning draw, and every
+// other cell from later draws, so no line can be complete before that draw.
+fn winning_board_numbers(params: &BingoParams, draws: &[u32], draw_index: usize, rng: &mut Rng) -> Vec<u32> {
+    let num_other_cells = params.width * (params.height - 1);
+    assert!(draw_index < draws.len(), "Draw {} is past the last of {} draws", draw_index, draws.len());
+    assert!(params.width <= draw_index + 1,
+            "A row of {} cannot be complete by draw {}", params.width, draw_index);
+    let later_draws = &draws[draw_index + 1..];
+    assert!(num_other_cells == 0 || !later_draws.is_empty(),
+            "No draws after draw {} to fill the rest of the board", draw_index);
+    assert!(!params.unique_within_board || num_other_cells <= later_draws.len(),
+            "Not enough draws after draw {} to fill the board with unique numbers", draw_index);
+
+    let mut winning_row = pick_unique(&draws[..draw_index], params.width - 1, rng);
+    winning_row.push(draws[draw_index]);
+    rng.shuffle(&mut winning_row);
+    let mut other_cells = random_board_numbers(params, later_draws, num_other_cells, rng).into_iter();
+
+    let winning_row_index = rng.below(params.height as u64) as usize;
+    (0..params.height)
+        .flat_map(|row_index| if row_index == winning_row_index {
+            winning_row.clone()
+        } else {
+            other_cells.by_ref().take(params.width).collect_vec()
+        })
+        .collect_vec()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::day4::{Board, parse_numbers_csv, play_all_boards, read_boards, ROWS_AND_COLUMNS};
+    use crate::day4gen::*;
+
+    fn parse_generated(lines: Vec<String>) -> (Vec<u32>, Vec<Board>) {
+        let mut input = lines.into_iter();
+        let numbers = parse_numbers_csv(&input.next().unwrap(), 1).unwrap();
+        (numbers, read_boards(input))
+    }
+
+    #[test]
+    fn generated_input_is_reproducible() {
+        let params = BingoParams::new(3, 42);
+        assert_eq!(generate_bingo_input(&params), generate_bingo_input(&params));
+        assert_ne!(generate_bingo_input(&params), generate_bingo_input(&BingoParams::new(3, 43)));
+    }
+
+    #[test]
+    fn generated_input_is_in_day4_format() {
+        let lines = generate_bingo_input(&BingoParams::new(2, 1));
+        assert_eq!(lines.len(), 1 + 2 * 6);
+        assert_eq!(lines[1], "");
+        assert_eq!(lines[2].len(), 5 * 2 + 4);
+        let (numbers, boards) = parse_generated(lines);
+        assert_eq!(numbers.iter().copied().sorted().collect_vec(), (0..=99).collect_vec());
+        assert_eq!(boards.len(), 2);
+    }
+
+    #[test]
+    fn generated_boards_have_requested_size_and_range() {
+        let mut params = BingoParams::new(4, 5);
+        params.width = 3;
+        params.height = 2;
+        params.number_range = 100..=120;
+        let (_numbers, boards) = parse_generated(generate_bingo_input(&params));
+        for board in boards {
+            assert_eq!(board.num_cells(), 6);
+            assert!(board.numbers().all(|num| (100..=120).contains(&num.unwrap())));
+            assert!(board.numbers().all_unique());
+        }
+    }
+
+    #[test]
+    fn non_unique_boards_may_repeat_numbers() {
+        let mut params = BingoParams::new(20, 9);
+        params.number_range = 1..=5;
+        params.unique_within_board = false;
+        let (_numbers, boards) = parse_generated(generate_bingo_input(&params));
+        assert!(boards.iter().any(|board| !board.numbers().all_unique()));
+    }
+
+    #[test]
+    fn pick_unique_draws_distinct_candidates() {
+        let mut rng = Rng::new(7);
+        let candidates = (100..200).collect_vec();
+        let picked = pick_unique(&candidates, 10, &mut rng);
+        assert_eq!(picked.len(), 10);
+        assert!(picked.iter().all_unique());
+        assert!(picked.iter().all(|number| candidates.contains(number)));
+        assert_eq!(pick_unique(&candidates, 100, &mut rng).into_iter().sorted().collect_vec(), candidates);
+    }
+
+    #[test]
+    #[should_panic(expected = "Cannot place 25 unique numbers from a range of 10")]
+    fn unique_boards_need_enough_numbers() {
+        let mut params = BingoParams::new(1, 0);
+        params.number_range = 1..=10;
+        generate_bingo_input(&params);
+    }
+
+    #[test]
+    fn guaranteed_board_wins_on_given_draw() {
+        for seed in 0..20 {
+            let mut params = BingoParams::new(10, seed);
+            params.guaranteed_win = Some(GuaranteedWin { board_index: 7, draw_index: 30 });
+            let (numbers, mut boards) = parse_generated(generate_bingo_input(&params));
+            let results = play_all_boards(&mut boards, &numbers, ROWS_AND_COLUMNS);
+            assert_eq!(results.board(7).win.unwrap().draw_index, 30);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "A row of 5 cannot be complete by draw 3")]
+    fn guaranteed_win_needs_enough_draws() {
+        let mut params = BingoParams::new(1, 0);
+        params.guaranteed_win = Some(GuaranteedWin { board_index: 0, draw_index: 3 });
+        generate_bingo_input(&params);
+    }
+}
@@ -0,0 +1,292 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use itertools::Itertools;
+use crate::day4::{Board, Cell, play_all_boards, read_board, ROWS_AND_COLUMNS};
+use crate::util::{Block, parse_list_columns, ParseError, read_blocks};
+
+// Reports what it can about malformed input rather than stopping at the first bad token.
+#[allow(dead_code)]
+pub fn day4_lint(input: impl Iterator<Item=String>) -> Vec<LintIssue> {
+    let lines = input.collect_vec();
+    let mut issues = vec![];
+
+    let mut draws = vec![];
+    for (column, number) in parse_list_columns::<u32>(lines.first().map_or("", |line| line), 1, ",") {
+        match number {
+            Ok(number) => draws.push(Draw { column, number }),
+            Err(error) => issues.push(LintIssue::unparsable(error)),
+        }
+    }
+
+    let board_blocks = read_blocks(lines.iter().skip(1).cloned(), 2);
+    let mut boards = vec![];
+    for (board_index, block) in board_blocks.iter().enumerate() {
+        issues.extend(lint_board_numbers(board_index, block));
+        match read_board(block.clone()) {
+            Ok(board) => boards.push((board_index, board)),
+            Err(error) => issues.push(LintIssue::unparsable(error)),
+        }
+    }
+
+    issues.extend(lint_draws(&draws, &board_blocks));
+    issues.extend(lint_unwinnable_boards(&draws, boards, &board_blocks));
+    issues.sort_by_key(|issue| (issue.line_number, issue.column));
+    issues
+}
+
+#[derive(Debug, PartialEq)]
+pub struct LintIssue {
+    pub line_number: usize,
+    pub column: Option<usize>,
+    pub kind: LintKind,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum LintKind {
+    Unparsable { message: String },
+    DuplicateOnBoard { board_index: usize, number: u32, first_line_number: usize },
+    RepeatedDraw { draw_index: usize, number: u32, first_draw_index: usize },
+    DrawOnNoBoard { draw_index: usize, number: u32 },
+    BoardNeverWins { board_index: usize },
+}
+
+struct Draw {
+    column: usize,
+    number: u32,
+}
+
+impl LintIssue {
+    fn unparsable(error: ParseError) -> Self {
+        LintIssue {
+            line_number: error.line_number,
+            column: Some(error.column),
+            kind: LintKind::Unparsable { message: error.message },
+        }
+    }
+}
+
+impl fmt::Display for LintIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.column {
+            Some(column) => write!(f, "line {}, column {}: ", self.line_number, column)?,
+            None => write!(f, "line {}: ", self.line_number)?,
+        }
+        match &self.kind {
+            LintKind::Unparsable { message } =>
+                write!(f, "{}", message),
+            LintKind::DuplicateOnBoard { board_index, number, first_line_number } =>
+                write!(f, "board {} repeats {} (first on line {})", board_index + 1, number, first_line_number),
+            LintKind::RepeatedDraw { draw_index, number, first_draw_index } =>
+                write!(f, "draw {} repeats {} (first drawn at draw {})", draw_index + 1, number, first_draw_index + 1),
+            LintKind::DrawOnNoBoard { draw_index, number } =>
+                write!(f, "draw {} ({}) is on no board", draw_index + 1, number),
+            LintKind::BoardNeverWins { board_index } =>
+                write!(f, "board {} can never win with these draws", board_index + 1),
+        }
+    }
+}
+
+// Tokens that don't parse are reported by read_board, so they're skipped here.
+fn board_cells(line: &str, line_number: usize) -> impl Iterator<Item=(usize, Cell)> {
+    parse_list_columns::<Cell>(line, line_number, " ").into_iter()
+        .filter_map(|(column, cell)| cell.ok().map(|cell| (column, cell)))
+}
+
+fn lint_draws(draws: &[Draw], board_blocks: &[Block]) -> Vec<LintIssue> {
+    let board_numbers: HashSet<u32> = board_blocks.iter()
+        .flat_map(|block| block.lines.iter().zip(block.first_line_number..))
+        .flat_map(|(line, line_number)| board_cells(line, line_number))
+        .filter_map(|(_column, cell)| cell.number())
+        .collect();
+    let mut first_draw_indexes = HashMap::new();
+    let mut issues = vec![];
+    for (draw_index, draw) in draws.iter().enumerate() {
+        let number = draw.number;
+        let column = Some(draw.column);
+        if let Some(&first_draw_index) = first_draw_indexes.get(&number) {
+            issues.push(LintIssue {
+                line_number: 1,
+                column,
+                kind: LintKind::RepeatedDraw { draw_index, number, first_draw_index },
+            });
+            continue;
+        }
+        first_draw_indexes.insert(number, draw_index);
+        if !board_numbers.contains(&number) {
+            issues.push(LintIssue { line_number: 1, column, kind: LintKind::DrawOnNoBoard { draw_index, number } });
+        }
+    }
+    issues
+}
+
+fn lint_board_numbers(board_index: usize, block: &Block) -> Vec<LintIssue> {
+    let mut first_line_numbers = HashMap::new();
+    let mut issues = vec![];
+    for (line, line_number) in block.lines.iter().zip(block.first_line_number..) {
+        for (column, cell) in board_cells(line, line_number) {
+            let Some(number) = cell.number() else { continue };
+            if let Some(&first_line_number) = first_line_numbers.get(&number) {
+                issues.push(LintIssue {
+                    line_number,
+                    column: Some(column),
+                    kind: LintKind::DuplicateOnBoard { board_index, number, first_line_number },
+                });
+            } else {
+                first_line_numbers.insert(number, line_number);
+            }
+        }
+    }
+    issues
+}
+
+// Only boards that parsed can be played; their indexes still count every board in the input.
+fn lint_unwinnable_boards(draws: &[Draw], boards: Vec<(usize, Board)>, board_blocks: &[Block]) -> Vec<LintIssue> {
+    let numbers = draws.iter().map(|draw| draw.number).collect_vec();
+    let (board_indexes, mut boards): (Vec<usize>, Vec<Board>) = boards.into_iter().unzip();
+    let results = play_all_boards(&mut boards, &numbers, ROWS_AND_COLUMNS);
+    results.never_won()
+        .map(|result| board_indexes[result.board_index])
+        .map(|board_index| LintIssue {
+            line_number: board_blocks[board_index].first_line_number,
+            column: None,
+            kind: LintKind::BoardNeverWins { board_index },
+        })
+        .collect_vec()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::day4lint::*;
+    use crate::util::to_string_iter;
+
+    #[test]
+    fn clean_input_has_no_issues() {
+        let input = to_string_iter(vec![
+            "1,2,3,4",
+            "",
+            "1 2",
+            "3 4",
+        ]);
+        assert_eq!(day4_lint(input), vec![]);
+    }
+
+    #[test]
+    fn report_duplicate_numbers_within_a_board() {
+        let input = to_string_iter(vec![
+            "1,2,3",
+            "",
+            "1 2",
+            "3 1",
+            "",
+            "3 2",
+            "1 3*",
+        ]);
+        assert_eq!(day4_lint(input), vec![
+            LintIssue { line_number: 4, column: Some(3), kind: LintKind::DuplicateOnBoard { board_index: 0, number: 1, first_line_number: 3 } },
+            LintIssue { line_number: 7, column: Some(3), kind: LintKind::DuplicateOnBoard { board_index: 1, number: 3, first_line_number: 6 } },
+        ]);
+    }
+
+    #[test]
+    fn report_repeated_draws_and_draws_on_no_board() {
+        let input = to_string_iter(vec![
+            "1,9,2,1,3,4",
+            "",
+            "1 2",
+            "3 4",
+        ]);
+        assert_eq!(day4_lint(input), vec![
+            LintIssue { line_number: 1, column: Some(3), kind: LintKind::DrawOnNoBoard { draw_index: 1, number: 9 } },
+            LintIssue { line_number: 1, column: Some(7), kind: LintKind::RepeatedDraw { draw_index: 3, number: 1, first_draw_index: 0 } },
+        ]);
+    }
+
+    #[test]
+    fn report_boards_that_never_win() {
+        let input = to_string_iter(vec![
+            "1,2,3",
+            "",
+            "",
+            "1 2",
+            "3 4",
+            "",
+            "5 6",
+            "7 8",
+        ]);
+        assert_eq!(day4_lint(input), vec![
+            LintIssue { line_number: 7, column: None, kind: LintKind::BoardNeverWins { board_index: 1 } },
+        ]);
+    }
+
+    #[test]
+    fn free_cells_are_not_duplicates() {
+        let input = to_string_iter(vec![
+            "1,2",
+            "",
+            "1 *",
+            "* 2",
+        ]);
+        assert_eq!(day4_lint(input), vec![]);
+    }
+
+    #[test]
+    fn display_issues() {
+        let issue = LintIssue { line_number: 4, column: Some(3), kind: LintKind::DuplicateOnBoard { board_index: 0, number: 1, first_line_number: 3 } };
+        assert_eq!(issue.to_string(), "line 4, column 3: board 1 repeats 1 (first on line 3)");
+        let issue = LintIssue { line_number: 1, column: Some(7), kind: LintKind::RepeatedDraw { draw_index: 3, number: 1, first_draw_index: 0 } };
+        assert_eq!(issue.to_string(), "line 1, column 7: draw 4 repeats 1 (first drawn at draw 1)");
+        let issue = LintIssue { line_number: 1, column: Some(3), kind: LintKind::DrawOnNoBoard { draw_index: 1, number: 9 } };
+        assert_eq!(issue.to_string(), "line 1, column 3: draw 2 (9) is on no board");
+        let issue = LintIssue { line_number: 7, column: None, kind: LintKind::BoardNeverWins { board_index: 1 } };
+        assert_eq!(issue.to_string(), "line 7: board 2 can never win with these draws");
+        let issue = LintIssue { line_number: 3, column: Some(5), kind: LintKind::Unparsable { message: "expected a value".to_string() } };
+        assert_eq!(issue.to_string(), "line 3, column 5: expected a value");
+    }
+
+    #[test]
+    fn empty_input_has_no_issues() {
+        assert_eq!(day4_lint(to_string_iter(vec![])), vec![]);
+    }
+
+    #[test]
+    fn report_unparsable_draws_and_keep_linting_the_rest() {
+        let input = to_string_iter(vec![
+            "1,x,2,,3,1",
+            "",
+            "1 2",
+            "3 4",
+        ]);
+        assert_eq!(day4_lint(input).iter().map(|issue| issue.to_string()).collect_vec(), vec![
+            "line 1, column 3: can't parse \"x\" as u32",
+            "line 1, column 7: expected a value",
+            "line 1, column 10: draw 4 repeats 1 (first drawn at draw 1)",
+        ]);
+    }
+
+    #[test]
+    fn report_unparsable_boards_and_keep_linting_the_rest() {
+        let input = to_string_iter(vec![
+            "1,2,3,4",
+            "",
+            "1 2",
+            "3",
+            "",
+            "1 x",
+            "",
+            "1 2",
+            "1 4",
+        ]);
+        assert_eq!(day4_lint(input).iter().map(|issue| issue.to_string()).collect_vec(), vec![
+            "line 4, column 2: row has 1 values, expected 2",
+            "line 6, column 3: can't parse \"x\" as Cell",
+            "line 9, column 1: board 3 repeats 1 (first on line 8)",
+        ]);
+    }
+
+    #[test]
+    fn boards_over_128_cells_are_linted_like_any_other() {
+        let rows = (0..11).map(|row| (1..=12).map(|x| row * 12 + x).join(" "));
+        let input = [(1..=12).join(","), String::new()].into_iter().chain(rows);
+        assert_eq!(day4_lint(input), vec![]);
+    }
+}
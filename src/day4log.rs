@@ -0,0 +1,360 @@
+use itertools::Itertools;
+use crate::day4::{Board, build_cell_indexes, CellMask, claim_completed_boards, parse_numbers_csv, read_board, read_boards, ROWS_AND_COLUMNS, WinPattern};
+use crate::util::{Block, Captures, match_pattern, ParseError, read_blocks};
+
+#[allow(dead_code)]
+pub fn day4_log(mut input: impl Iterator<Item=String>) -> GameLog {
+    let numbers = parse_numbers_csv(&input.next().unwrap(), 1).unwrap_or_else(|error| panic!("{}", error));
+    let mut boards = read_boards(input);
+    play_with_log(&mut boards, &numbers, ROWS_AND_COLUMNS)
+}
+
+pub fn play_with_log(boards: &mut [Board], numbers: &[u32], patterns: &[WinPattern]) -> GameLog {
+    let cell_indexes = build_cell_indexes(boards);
+    let mut log = GameLog { boards: boards.to_vec(), events: vec![] };
+    for (draw_index, &number) in numbers.iter().enumerate() {
+        let mut marks = vec![];
+        let mut winners = vec![];
+        for &(board_index, cell_index) in cell_indexes.get(&number).into_iter().flatten() {
+            let board = &mut boards[board_index];
+            if board.has_won() {
+                continue;
+            }
+            marks.push((board_index, cell_index));
+            if board.play_cell(cell_index, patterns).is_some() {
+                winners.push(board_index);
+            }
+        }
+        if draw_index == 0 {
+            winners.extend(claim_completed_boards(boards, patterns));
+            winners.sort_unstable();
+        }
+        log.events.push(GameEvent::Draw { draw_index, number, marks });
+        log.events.extend(winners.into_iter().map(|board_index| GameEvent::Win {
+            draw_index,
+            number,
+            board_index,
+            pattern: boards[board_index].winning_pattern().unwrap().clone(),
+            line: boards[board_index].winning_line().unwrap(),
+            score: boards[board_index].sum_unmarked_numbers() * number,
+        }));
+    }
+    log
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum GameEvent {
+    Draw {
+        draw_index: usize,
+        number: u32,
+        marks: Vec<(usize, usize)>,
+    },
+    Win {
+        draw_index: usize,
+        number: u32,
+        board_index: usize,
+        pattern: WinPattern,
+        line: Vec<usize>,
+        score: u32,
+    },
+}
+
+#[allow(dead_code)]
+impl GameEvent {
+    // A draw is followed by a line for each cell it marked. The draw a win belongs to is the
+    // one before it, so only the draw line holds the number.
+    pub fn to_lines(&self) -> Vec<String> {
+        match self {
+            GameEvent::Draw { number, marks, .. } => [format!("draw {}", number)].into_iter()
+                .chain(marks.iter().map(|(board_index, cell_index)| format!("mark board {} cell {}", board_index, cell_index)))
+                .collect_vec(),
+            GameEvent::Win { board_index, pattern, line, score, .. } => vec![format!(
+                "win board {} pattern {} line {} score {}", board_index, pattern.name(), line.iter().join(","), score)],
+        }
+    }
+}
+
+// A "board" line over the board's rows, written as the puzzle input writes them.
+fn board_to_lines(board: &Board) -> Vec<String> {
+    let grid = board.number_grid();
+    [String::from("board")].into_iter()
+        .chain((0..grid.height()).map(|y| grid.row_indexes(y).map(|cell_index| board.cell(cell_index)).join(" ")))
+        .collect_vec()
+}
+
+// The boards as they were before the first draw, and everything that happened to them.
+#[derive(Debug, PartialEq)]
+pub struct GameLog {
+    boards: Vec<Board>,
+    events: Vec<GameEvent>,
+}
+
+#[allow(dead_code)]
+impl GameLog {
+    pub fn iter(&self) -> impl Iterator<Item=&GameEvent> {
+        self.events.iter()
+    }
+
+    pub fn wins(&self) -> impl Iterator<Item=&GameEvent> {
+        self.events.iter()
+            .filter(|event| matches!(event, GameEvent::Win { .. }))
+    }
+
+    // Each board in its own block, then a block of events.
+    pub fn to_lines(&self) -> Vec<String> {
+        let blocks = self.boards.iter().map(board_to_lines)
+            .chain([self.events.iter().flat_map(|event| event.to_lines()).collect_vec()])
+            .filter(|block| !block.is_empty());
+        Itertools::intersperse(blocks, vec![String::new()]).flatten().collect_vec()
+    }
+
+    pub fn from_lines(input: impl Iterator<Item=String>) -> Result<GameLog, ParseError> {
+        let mut log = GameLog { boards: vec![], events: vec![] };
+        for block in read_blocks(input, 1) {
+            let header = &block.lines[0];
+            if header.trim() == "board" {
+                if !log.events.is_empty() {
+                    let message = "board is after the events".to_string();
+                    return Err(ParseError { line_number: block.first_line_number, column: 1, message });
+                }
+                log.boards.push(read_board(Block {
+                    first_line_number: block.first_line_number + 1,
+                    lines: block.lines[1..].to_vec(),
+                })?);
+            } else {
+                for (line_index, line) in block.lines.iter().enumerate() {
+                    log.read_event(line, block.first_line_number + line_index)?;
+                }
+            }
+        }
+        Ok(log)
+    }
+
+    fn read_event(&mut self, line: &str, line_number: usize) -> Result<(), ParseError> {
+        let last_draw = self.events.iter().rev()
+            .find_map(|event| match event {
+                GameEvent::Draw { draw_index, number, .. } => Some((*draw_index, *number)),
+                _ => None,
+            });
+        match line.split_whitespace().next() {
+            Some("draw") => {
+                let captures = match_pattern(line, line_number, "draw {}")?;
+                let draw_index = last_draw.map_or(0, |(draw_index, _number)| draw_index + 1);
+                self.events.push(GameEvent::Draw { draw_index, number: captures.parse(0)?, marks: vec![] });
+            }
+            Some("mark") => {
+                let captures = match_pattern(line, line_number, "mark board {} cell {}")?;
+                let board_index = self.board_index(&captures, 0)?;
+                let cell_index = captures.parse(1)?;
+                if cell_index >= self.boards[board_index].num_cells() {
+                    return Err(captures.error(1, format!("no cell {} on board {}", cell_index, board_index)));
+                }
+                match self.events.last_mut() {
+                    Some(GameEvent::Draw { marks, .. }) => marks.push((board_index, cell_index)),
+                    _ => return Err(ParseError { line_number, column: 1, message: "mark is not after a draw".to_string() }),
+                }
+            }
+            Some("win") => {
+                let captures = match_pattern(line, line_number, "win board {} pattern {} line {} score {}")?;
+                let (draw_index, number) = last_draw
+                    .ok_or_else(|| ParseError { line_number, column: 1, message: "win is before any draw".to_string() })?;
+                let board_index = self.board_index(&captures, 0)?;
+                let line = captures.parse_list::<usize>(2, ",")?;
+                let num_cells = self.boards[board_index].num_cells();
+                if let Some(cell_index) = line.iter().find(|&&cell_index| cell_index >= num_cells) {
+                    return Err(captures.error(2, format!("no cell {} on board {}", cell_index, board_index)));
+                }
+                // A mask win's line is the whole mask.
+                let pattern = match captures.str(1) {
+                    "row" => WinPattern::Row,
+                    "column" => WinPattern::Column,
+                    "diagonal" => WinPattern::Diagonal,
+                    "four corners" => WinPattern::FourCorners,
+                    "X" => WinPattern::X,
+                    "blackout" => WinPattern::Blackout,
+                    "mask" => WinPattern::Mask(CellMask::from_indexes(line.iter().copied())),
+                    other => return Err(captures.error(1, format!("unknown pattern {:?}", other))),
+                };
+                self.events.push(GameEvent::Win { draw_index, number, board_index, pattern, line, score: captures.parse(3)? });
+            }
+            _ => return Err(ParseError { line_number, column: 1, message: format!("unknown event {:?}", line.trim()) }),
+        }
+        Ok(())
+    }
+
+    fn board_index(&self, captures: &Captures, capture_index: usize) -> Result<usize, ParseError> {
+        let board_index = captures.parse(capture_index)?;
+        if board_index >= self.boards.len() {
+            return Err(captures.error(capture_index, format!("no board {}", board_index)));
+        }
+        Ok(board_index)
+    }
+
+    // The boards as they were at the end of the game.
+    pub fn replay(&self) -> Vec<Board> {
+        let mut boards = self.boards.clone();
+        for event in &self.events {
+            match event {
+                GameEvent::Draw { marks, .. } => marks.iter()
+                    .for_each(|&(board_index, cell_index)| boards[board_index].mark_cell(cell_index)),
+                GameEvent::Win { board_index, pattern, line, .. } =>
+                    boards[*board_index].record_win(pattern.clone(), line),
+            }
+        }
+        boards
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::day4log::*;
+    use crate::util::to_string_iter;
+
+    fn example_input() -> impl Iterator<Item=String> {
+        to_string_iter(vec![
+            "7,4,9,5,11,17,23,2,0,14,21,24,10,16,13,6,15,25,12,22,18,20,8,19,3,26,1",
+            "",
+            "22 13 17 11  0",
+            " 8  2 23  4 24",
+            "21  9 14 16  7",
+            " 6 10  3 18  5",
+            " 1 12 20 15 19",
+            "",
+            " 3 15  0  2 22",
+            " 9 18 13 17  5",
+            "19  8  7 25 23",
+            "20 11 10 24  4",
+            "14 21 16 12  6",
+            "",
+            "14 21 17 24  4",
+            "10 16 15  9 19",
+            "18  8 23 26 20",
+            "22 11 13  6  5",
+            " 2  0 12  3  7",
+        ])
+    }
+
+    fn small_boards() -> Vec<Board> {
+        read_boards(to_string_iter(vec![
+            "",
+            "1 2",
+            "3 4",
+            "",
+            "2 5",
+            "6 1",
+        ]))
+    }
+
+    #[test]
+    fn log_records_draw_marks_and_wins() {
+        let mut boards = small_boards();
+        let log = play_with_log(&mut boards, &[2, 7, 1, 4], ROWS_AND_COLUMNS);
+        assert_eq!(log.iter().cloned().collect_vec(), vec![
+            GameEvent::Draw { draw_index: 0, number: 2, marks: vec![(0, 1), (1, 0)] },
+            GameEvent::Draw { draw_index: 1, number: 7, marks: vec![] },
+            GameEvent::Draw { draw_index: 2, number: 1, marks: vec![(0, 0), (1, 3)] },
+            GameEvent::Win { draw_index: 2, number: 1, board_index: 0, pattern: WinPattern::Row, line: vec![0, 1], score: 7 },
+            GameEvent::Draw { draw_index: 3, number: 4, marks: vec![] },
+        ]);
+    }
+
+    #[test]
+    fn example_wins_in_order() {
+        let log = day4_log(example_input());
+        let scores = log.wins()
+            .map(|event| match event {
+                GameEvent::Win { board_index, score, .. } => (*board_index, *score),
+                _ => unreachable!(),
+            })
+            .collect_vec();
+        assert_eq!(scores, vec![(2, 4512), (0, 2192), (1, 1924)]);
+    }
+
+    #[test]
+    fn example_winning_line() {
+        let log = day4_log(example_input());
+        let first_win = log.wins().next().cloned();
+        match first_win {
+            Some(GameEvent::Win { number, pattern, line, .. }) => {
+                assert_eq!(number, 24);
+                assert_eq!(pattern, WinPattern::Row);
+                assert_eq!(line, vec![0, 1, 2, 3, 4]);
+            }
+            _ => panic!("No winning board"),
+        }
+    }
+
+    #[test]
+    fn log_as_text() {
+        let mut boards = small_boards();
+        let log = play_with_log(&mut boards, &[2, 1], ROWS_AND_COLUMNS);
+        assert_eq!(log.to_lines(), vec![
+            "board",
+            "1 2",
+            "3 4",
+            "",
+            "board",
+            "2 5",
+            "6 1",
+            "",
+            "draw 2",
+            "mark board 0 cell 1",
+            "mark board 1 cell 0",
+            "draw 1",
+            "mark board 0 cell 0",
+            "mark board 1 cell 3",
+            "win board 0 pattern row line 0,1 score 7",
+        ]);
+    }
+
+    #[test]
+    fn text_log_loads_back() {
+        let log = day4_log(example_input());
+        assert_eq!(GameLog::from_lines(log.to_lines().into_iter()), Ok(log));
+    }
+
+    #[test]
+    fn free_cells_and_mask_wins_survive_text() {
+        let mut boards = read_boards(to_string_iter(vec!["", "1 *", "3 4*"]));
+        let pattern = WinPattern::mask(&[true, false, false, true]);
+        let log = play_with_log(&mut boards, &[3, 1], std::slice::from_ref(&pattern));
+        let lines = log.to_lines();
+        assert_eq!(lines[1..3], ["1 *", "3 4*"]);
+        assert!(lines.contains(&"win board 0 pattern mask line 0,3 score 0".to_string()));
+        let loaded = GameLog::from_lines(lines.into_iter()).unwrap();
+        assert_eq!(loaded, log);
+        assert_eq!(loaded.replay()[0].winning_pattern(), Some(&pattern));
+    }
+
+    #[test]
+    fn bad_log_reports_line_and_column() {
+        let read_error = |lines: Vec<&'static str>| GameLog::from_lines(to_string_iter(lines)).unwrap_err().to_string();
+        assert_eq!(read_error(vec!["board", "1 2", "", "draw 1", "mark board 0 cell 2"]),
+                   "line 5, column 19: no cell 2 on board 0");
+        assert_eq!(read_error(vec!["board", "1 2", "", "draw 1", "mark board 1 cell 0"]),
+                   "line 5, column 12: no board 1");
+        assert_eq!(read_error(vec!["board", "1 2", "", "win board 0 pattern row line 0,1 score 0"]),
+                   "line 4, column 1: win is before any draw");
+        assert_eq!(read_error(vec!["board", "1 2", "", "draw 1", "win board 0 pattern zigzag line 0 score 0"]),
+                   "line 5, column 21: unknown pattern \"zigzag\"");
+        assert_eq!(read_error(vec!["board", "1 2", "", "draw 1", "win board 0 pattern row line 0,x score 0"]),
+                   "line 5, column 32: can't parse \"x\" as usize");
+        assert_eq!(read_error(vec!["board", "1 x", "", "draw 1"]),
+                   "line 2, column 3: can't parse \"x\" as Cell");
+        assert_eq!(read_error(vec!["board", "1 2", "", "draw 1", "", "board", "3 4"]),
+                   "line 6, column 1: board is after the events");
+        assert_eq!(read_error(vec!["board", "1 2", "", "undo 1"]),
+                   "line 4, column 1: unknown event \"undo 1\"");
+    }
+
+    #[test]
+    fn replay_reproduces_final_board_states() {
+        let mut input = example_input();
+        let numbers = parse_numbers_csv(&input.next().unwrap(), 1).unwrap();
+        let mut played_boards = read_boards(input);
+        let log = play_with_log(&mut played_boards, &numbers, ROWS_AND_COLUMNS);
+
+        let loaded_log = GameLog::from_lines(log.to_lines().into_iter()).unwrap();
+        assert_eq!(loaded_log.replay(), played_boards);
+    }
+}
@@ -0,0 +1,270 @@
+use std::collections::HashMap;
+use itertools::Itertools;
+use crate::day4::{Board, build_cell_indexes, claim_completed_boards, parse_numbers_csv, play_number_on_all_boards, read_boards, ROWS_AND_COLUMNS, WinPattern};
+use crate::util::Rng;
+
+const MAX_EXACT_POOL_SIZE: usize = 9;
+const Z_95: f64 = 1.96;
+
+#[allow(dead_code)]
+pub fn day4_win_probabilities(mut input: impl Iterator<Item=String>, num_trials: usize, seed: u64) -> WinProbabilities {
+    let pool = parse_numbers_csv(&input.next().unwrap(), 1).unwrap_or_else(|error| panic!("{}", error));
+    let boards = read_boards(input);
+    // Every order is enumerated when that takes no more games than the trials asked for.
+    let num_orders = (1..=pool.len()).try_fold(1usize, |num_orders, n| num_orders.checked_mul(n));
+    if pool.len() <= MAX_EXACT_POOL_SIZE && num_orders.is_some_and(|num_orders| num_orders <= num_trials) {
+        exact_first_wins(&boards, &pool, ROWS_AND_COLUMNS)
+    } else {
+        simulate_first_wins(&boards, &pool, ROWS_AND_COLUMNS, num_trials, seed)
+    }
+}
+
+pub fn simulate_first_wins(boards: &[Board], pool: &[u32], patterns: &[WinPattern], num_trials: usize, seed: u64) -> WinProbabilities {
+    let cell_indexes = build_cell_indexes(boards);
+    let mut rng = Rng::new(seed);
+    let mut draws = pool.to_vec();
+    let mut tally = WinProbabilities::new(boards.len(), false);
+    for _trial in 0..num_trials {
+        rng.shuffle(&mut draws);
+        tally.record(play_to_first_win(boards, &cell_indexes, &draws, patterns));
+    }
+    tally
+}
+
+pub fn exact_first_wins(boards: &[Board], pool: &[u32], patterns: &[WinPattern]) -> WinProbabilities {
+    assert!(pool.len() <= MAX_EXACT_POOL_SIZE,
+            "Pool of {} numbers is too large for exact enumeration", pool.len());
+    let cell_indexes = build_cell_indexes(boards);
+    let mut tally = WinProbabilities::new(boards.len(), true);
+    for draws in pool.iter().copied().permutations(pool.len()) {
+        tally.record(play_to_first_win(boards, &cell_indexes, &draws, patterns));
+    }
+    tally
+}
+
+// Every board that wins on the first winning draw, and how many draws that took.
+fn play_to_first_win(boards: &[Board], cell_indexes: &HashMap<u32, Vec<(usize, usize)>>, draws: &[u32], patterns: &[WinPattern]) -> Option<(Vec<usize>, usize)> {
+    let mut boards = boards.to_vec();
+    for (draw_index, &number) in draws.iter().enumerate() {
+        let mut winning_board_indexes = play_number_on_all_boards(number, &mut boards, cell_indexes, patterns);
+        if draw_index == 0 {
+            winning_board_indexes.extend(claim_completed_boards(&mut boards, patterns));
+            winning_board_indexes.sort_unstable();
+        }
+        if !winning_board_indexes.is_empty() {
+            return Some((winning_board_indexes, draw_index + 1));
+        }
+    }
+    None
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Estimate {
+    pub value: f64,
+    pub low: f64,
+    pub high: f64,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct WinProbabilities {
+    num_trials: usize,
+    first_win_counts: Vec<usize>,
+    num_ties: usize,
+    num_no_winner: usize,
+    draw_count_sum: f64,
+    draw_count_sum_of_squares: f64,
+    is_exact: bool,
+}
+
+#[allow(dead_code)]
+impl WinProbabilities {
+    fn new(num_boards: usize, is_exact: bool) -> Self {
+        WinProbabilities {
+            num_trials: 0,
+            first_win_counts: vec![0; num_boards],
+            num_ties: 0,
+            num_no_winner: 0,
+            draw_count_sum: 0.0,
+            draw_count_sum_of_squares: 0.0,
+            is_exact,
+        }
+    }
+
+    // A draw that completes several boards at once is a tie, not a win for any one of them.
+    fn record(&mut self, first_win: Option<(Vec<usize>, usize)>) {
+        self.num_trials += 1;
+        if let Some((winning_board_indexes, num_draws)) = first_win {
+            if let [board_index] = winning_board_indexes[..] {
+                self.first_win_counts[board_index] += 1;
+            } else {
+                self.num_ties += 1;
+            }
+            self.draw_count_sum += num_draws as f64;
+            self.draw_count_sum_of_squares += (num_draws * num_draws) as f64;
+        } else {
+            self.num_no_winner += 1;
+        }
+    }
+
+    pub fn num_trials(&self) -> usize {
+        self.num_trials
+    }
+
+    pub fn is_exact(&self) -> bool {
+        self.is_exact
+    }
+
+    pub fn first_win_probability(&self, board_index: usize) -> Estimate {
+        self.proportion(self.first_win_counts[board_index])
+    }
+
+    pub fn tie_probability(&self) -> Estimate {
+        self.proportion(self.num_ties)
+    }
+
+    pub fn no_winner_probability(&self) -> Estimate {
+        self.proportion(self.num_no_winner)
+    }
+
+    pub fn expected_draws_to_first_win(&self) -> Option<Estimate> {
+        let num_wins = (self.num_trials - self.num_no_winner) as f64;
+        if num_wins == 0.0 {
+            return None;
+        }
+        let mean = self.draw_count_sum / num_wins;
+        if self.is_exact || num_wins < 2.0 {
+            return Some(Estimate { value: mean, low: mean, high: mean });
+        }
+        let variance = (self.draw_count_sum_of_squares - num_wins * mean * mean) / (num_wins - 1.0);
+        let margin = Z_95 * (variance.max(0.0) / num_wins).sqrt();
+        Some(Estimate { value: mean, low: mean - margin, high: mean + margin })
+    }
+
+    // Wilson score interval, which behaves at proportions near 0 and 1.
+    fn proportion(&self, count: usize) -> Estimate {
+        if self.num_trials == 0 {
+            return Estimate { value: 0.0, low: 0.0, high: 1.0 };
+        }
+        let n = self.num_trials as f64;
+        let p = count as f64 / n;
+        if self.is_exact {
+            return Estimate { value: p, low: p, high: p };
+        }
+        let z2 = Z_95 * Z_95;
+        let centre = (p + z2 / (2.0 * n)) / (1.0 + z2 / n);
+        let margin = Z_95 * (p * (1.0 - p) / n + z2 / (4.0 * n * n)).sqrt() / (1.0 + z2 / n);
+        Estimate { value: p, low: (centre - margin).max(0.0), high: (centre + margin).min(1.0) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::day4sim::*;
+    use crate::util::to_string_iter;
+
+    const ROWS: &[WinPattern] = &[WinPattern::Row];
+
+    fn two_small_boards() -> Vec<Board> {
+        read_boards(to_string_iter(vec![
+            "",
+            "1 2",
+            "",
+            "3 4 5",
+        ]))
+    }
+
+    #[test]
+    fn exact_probabilities_for_small_pool() {
+        // Board 1 wins first exactly when the last number drawn is 1 or 2.
+        let probabilities = exact_first_wins(&two_small_boards(), &[1, 2, 3, 4, 5], ROWS);
+        assert_eq!(probabilities.num_trials(), 120);
+        assert_eq!(probabilities.first_win_probability(0).value, 0.6);
+        assert_eq!(probabilities.first_win_probability(1).value, 0.4);
+        assert_eq!(probabilities.no_winner_probability().value, 0.0);
+        assert!(probabilities.is_exact());
+    }
+
+    #[test]
+    fn exact_expected_draws_for_single_board() {
+        let boards = read_boards(to_string_iter(vec!["", "1 2"]));
+        let probabilities = exact_first_wins(&boards, &[1, 2, 3], ROWS);
+        // The second of 1 and 2 arrives on draw 2 (2 of 6 orders) or draw 3 (4 of 6).
+        let expected = probabilities.expected_draws_to_first_win().unwrap();
+        assert!((expected.value - 8.0 / 3.0).abs() < 1e-9);
+        assert_eq!(expected.low, expected.high);
+    }
+
+    #[test]
+    fn simulation_is_reproducible_for_a_seed() {
+        let boards = two_small_boards();
+        let pool = (1..=12).collect_vec();
+        assert_eq!(simulate_first_wins(&boards, &pool, ROWS, 200, 7),
+                   simulate_first_wins(&boards, &pool, ROWS, 200, 7));
+    }
+
+    #[test]
+    fn simulation_interval_contains_exact_probability() {
+        let boards = two_small_boards();
+        let pool = [1, 2, 3, 4, 5];
+        let simulated = simulate_first_wins(&boards, &pool, ROWS, 2000, 11);
+        let estimate = simulated.first_win_probability(0);
+        assert!(estimate.low <= 0.6 && 0.6 <= estimate.high, "{:?}", estimate);
+        assert!(estimate.low < estimate.value && estimate.value < estimate.high);
+        let draws = simulated.expected_draws_to_first_win().unwrap();
+        assert!(draws.low < draws.value && draws.value < draws.high);
+    }
+
+    #[test]
+    fn pool_without_winning_numbers_has_no_winner() {
+        let probabilities = exact_first_wins(&two_small_boards(), &[1, 3, 4], ROWS);
+        assert_eq!(probabilities.no_winner_probability().value, 1.0);
+        assert_eq!(probabilities.expected_draws_to_first_win(), None);
+    }
+
+    #[test]
+    fn day4_uses_exact_answer_for_small_pools() {
+        let input = to_string_iter(vec![
+            "1,2,3,4,5",
+            "",
+            "1 2",
+            "",
+            "3 4 5",
+        ]);
+        // With single-row boards every drawn number completes a column.
+        let probabilities = day4_win_probabilities(input, 120, 0);
+        assert!(probabilities.is_exact());
+        assert_eq!(probabilities.first_win_probability(1).value, 0.6);
+        assert_eq!(probabilities.expected_draws_to_first_win().unwrap().value, 1.0);
+    }
+
+    #[test]
+    fn day4_simulates_when_trials_are_fewer_than_orders() {
+        let input = to_string_iter(vec![
+            "1,2,3,4,5",
+            "",
+            "1 2",
+            "",
+            "3 4 5",
+        ]);
+        let probabilities = day4_win_probabilities(input, 10, 0);
+        assert!(!probabilities.is_exact());
+        assert_eq!(probabilities.num_trials(), 10);
+    }
+
+    #[test]
+    fn simultaneous_wins_count_as_ties() {
+        // Board 0 wins alone when 1 is drawn first; after 2, drawing 1 completes both boards.
+        let boards = read_boards(to_string_iter(vec!["", "1", "", "1 2"]));
+        let probabilities = exact_first_wins(&boards, &[1, 2], ROWS);
+        assert_eq!(probabilities.first_win_probability(0).value, 0.5);
+        assert_eq!(probabilities.first_win_probability(1).value, 0.0);
+        assert_eq!(probabilities.tie_probability().value, 0.5);
+    }
+
+    #[test]
+    fn no_trials_gives_no_information() {
+        let probabilities = simulate_first_wins(&two_small_boards(), &[1, 2, 3], ROWS, 0, 1);
+        assert_eq!(probabilities.first_win_probability(0), Estimate { value: 0.0, low: 0.0, high: 1.0 });
+        assert_eq!(probabilities.expected_draws_to_first_win(), None);
+    }
+}
@@ -0,0 +1,114 @@
+use crate::day4::{GameResults, parse_numbers_csv, play_all_boards, read_boards, ROWS_AND_COLUMNS};
+
+#[allow(dead_code)]
+pub fn day4_results(mut input: impl Iterator<Item=String>) -> GameResults {
+    let numbers = parse_numbers_csv(&input.next().unwrap(), 1).unwrap_or_else(|error| panic!("{}", error));
+    let mut boards = read_boards(input);
+    play_all_boards(&mut boards, &numbers, ROWS_AND_COLUMNS)
+}
+
+#[cfg(test)]
+mod tests {
+    use itertools::Itertools;
+    use crate::day4::{BoardResult, BoardWin};
+    use crate::day4stats::*;
+    use crate::util::to_string_iter;
+
+    fn example_input() -> impl Iterator<Item=String> {
+        to_string_iter(vec![
+            "7,4,9,5,11,17,23,2,0,14,21,24,10,16,13,6,15,25,12,22,18,20,8,19,3,26,1",
+            "",
+            "22 13 17 11  0",
+            " 8  2 23  4 24",
+            "21  9 14 16  7",
+            " 6 10  3 18  5",
+            " 1 12 20 15 19",
+            "",
+            " 3 15  0  2 22",
+            " 9 18 13 17  5",
+            "19  8  7 25 23",
+            "20 11 10 24  4",
+            "14 21 16 12  6",
+            "",
+            "14 21 17 24  4",
+            "10 16 15  9 19",
+            "18  8 23 26 20",
+            "22 11 13  6  5",
+            " 2  0 12  3  7",
+        ])
+    }
+
+    #[test]
+    fn example_first_and_last_winners_match_day4a_and_day4b() {
+        let results = day4_results(example_input());
+        assert_eq!(results.first_winner().unwrap().win.unwrap().score, 4512);
+        assert_eq!(results.last_winner().unwrap().win.unwrap().score, 1924);
+    }
+
+    #[test]
+    fn example_win_order() {
+        let results = day4_results(example_input());
+        assert_eq!(results.winners().map(|result| result.board_index).collect_vec(), vec![2, 0, 1]);
+        assert_eq!(results.median_winner().unwrap().board_index, 0);
+        assert_eq!(results.nth_winner(1).unwrap().win.unwrap().draw_index, 13);
+        assert_eq!(results.nth_winner(3), None);
+    }
+
+    #[test]
+    fn example_board_result() {
+        let results = day4_results(example_input());
+        assert_eq!(*results.board(2), BoardResult {
+            board_index: 2,
+            win: Some(BoardWin { draw_index: 11, number: 24, score: 4512 }),
+            num_marked: 12,
+        });
+    }
+
+    #[test]
+    fn boards_that_never_win_are_included() {
+        let mut boards = read_boards(to_string_iter(vec![
+            "",
+            "1 2",
+            "3 4",
+            "",
+            "5 6",
+            "7 8",
+        ]));
+        let results = play_all_boards(&mut boards, &[5, 1, 2], ROWS_AND_COLUMNS);
+        assert_eq!(results.num_winners(), 1);
+        assert_eq!(results.last_winner().unwrap().board_index, 0);
+        assert_eq!(results.never_won().collect_vec(), vec![&BoardResult {
+            board_index: 1,
+            win: None,
+            num_marked: 1,
+        }]);
+    }
+
+    #[test]
+    fn last_winner_is_the_first_board_to_win_on_the_last_winning_draw() {
+        let mut boards = read_boards(to_string_iter(vec![
+            "",
+            "1 9",
+            "2 9",
+            "",
+            "1 8",
+            "2 8",
+        ]));
+        let results = play_all_boards(&mut boards, &[1, 2], ROWS_AND_COLUMNS);
+        assert_eq!(results.winners().map(|result| result.board_index).collect_vec(), vec![0, 1]);
+        assert_eq!(results.last_winner().unwrap().board_index, 0);
+        assert_eq!(results.last_winner().unwrap().win.unwrap().score, 36);
+    }
+
+    #[test]
+    fn no_winners() {
+        let mut boards = read_boards(to_string_iter(vec![
+            "",
+            "1 2",
+        ]));
+        let results = play_all_boards(&mut boards, &[3], ROWS_AND_COLUMNS);
+        assert_eq!(results.first_winner(), None);
+        assert_eq!(results.last_winner(), None);
+        assert_eq!(results.median_winner(), None);
+    }
+}
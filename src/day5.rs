@@ -1,8 +1,12 @@
+use std::collections::{BTreeMap, HashMap};
+use itertools::Itertools;
+use crate::util::{Grid, match_pattern, ParseError};
+
 #[allow(dead_code)]
 pub fn day5a(input: impl Iterator<Item=String>) -> (&'static str, i32) {
-    let mut loc_grid = LocationGrid::new();
-    input
-        .map(|line| parse_input_line(&line))
+    let vent_lines = read_vent_lines(input);
+    let mut loc_grid = LocationGrid::for_vent_lines(&vent_lines);
+    vent_lines.into_iter()
         .for_each(|vent_line| loc_grid.add_non_diagonal_vent_line(vent_line));
     let answer = loc_grid.num_dangerous_locs();
     ("day5a", answer as i32)
@@ -10,25 +14,62 @@ pub fn day5a(input: impl Iterator<Item=String>) -> (&'static str, i32) {
 
 #[allow(dead_code)]
 pub fn day5b(input: impl Iterator<Item=String>) -> (&'static str, i32) {
-    let mut loc_grid = LocationGrid::new();
-    input
-        .map(|line| parse_input_line(&line))
+    let vent_lines = read_vent_lines(input);
+    let mut loc_grid = LocationGrid::for_vent_lines(&vent_lines);
+    vent_lines.into_iter()
         .for_each(|vent_line| loc_grid.add_vent_line(vent_line));
     let answer = loc_grid.num_dangerous_locs();
     ("day5b", answer as i32)
 }
 
-fn parse_input_line(line: &str) -> LineSegment {
-    let mut nums = line
-        .split(" -> ")
-        .map(|loc_str| loc_str.split(","))
-        .flatten()
-        .map(|num_str| num_str.parse::<u32>().unwrap());
-    LineSegment(Loc::new(nums.next().unwrap(), nums.next().unwrap()),
-                Loc::new(nums.next().unwrap(), nums.next().unwrap()))
+#[allow(dead_code)]
+pub fn day5b_with_rasterisation(input: impl Iterator<Item=String>, rasterisation: Rasterisation) -> (&'static str, i32) {
+    let vent_lines = read_vent_lines(input);
+    let mut loc_grid = LocationGrid::for_vent_lines(&vent_lines);
+    vent_lines.into_iter()
+        .for_each(|vent_line| loc_grid.add_rasterised_vent_line(vent_line, rasterisation));
+    let answer = loc_grid.num_dangerous_locs();
+    ("day5b", answer as i32)
+}
+
+#[allow(dead_code)]
+pub fn day5b_with_danger_threshold(input: impl Iterator<Item=String>, danger_threshold: u16) -> (&'static str, i32) {
+    let vent_lines = read_vent_lines(input);
+    let mut loc_grid = LocationGrid::for_vent_lines(&vent_lines).with_danger_threshold(danger_threshold);
+    vent_lines.into_iter()
+        .for_each(|vent_line| loc_grid.add_vent_line(vent_line));
+    let answer = loc_grid.num_dangerous_locs();
+    ("day5b", answer as i32)
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Rasterisation {
+    // One loc per step along the major axis.
+    Bresenham,
+    // Every loc whose cell the line passes through, including both cells beside a corner it crosses.
+    Supercover,
+    // Only the locs that lie exactly on the line.
+    ExactLatticePoints,
+}
+
+// What day5b uses for lines that aren't horizontal, vertical or at 45 degrees. It picks
+// the same locs as stepping along a 45 degree line.
+pub const DEFAULT_RASTERISATION: Rasterisation = Rasterisation::Bresenham;
+
+pub fn read_vent_lines(input: impl Iterator<Item=String>) -> Vec<LineSegment> {
+    input.enumerate()
+        .map(|(line_index, line)| parse_input_line(&line, line_index + 1).unwrap_or_else(|error| panic!("{}", error)))
+        .collect_vec()
 }
 
-fn from_to(v1: u32, v2: u32) -> Box<dyn Iterator<Item=u32>> {
+pub fn parse_input_line(line: &str, line_number: usize) -> Result<LineSegment, ParseError> {
+    let captures = match_pattern(line, line_number, "{},{} -> {},{}")?;
+    Ok(LineSegment(Loc::new(captures.parse(0)?, captures.parse(1)?),
+                   Loc::new(captures.parse(2)?, captures.parse(3)?)))
+}
+
+fn from_to(v1: i64, v2: i64) -> Box<dyn Iterator<Item=i64>> {
     if v1 <= v2 {
         Box::new(v1..=v2)
     } else {
@@ -36,70 +77,253 @@ fn from_to(v1: u32, v2: u32) -> Box<dyn Iterator<Item=u32>> {
     }
 }
 
-struct LocationGrid {
-    rows: Vec<Vec<u16>>,
+// Use the sparse grid when the bounding box has this many times more cells than the vent lines cover.
+const SPARSE_AREA_RATIO: u64 = 64;
+pub const DEFAULT_DANGER_THRESHOLD: u16 = 2;
+
+pub struct LocationGrid {
+    vent_counts: VentCounts,
+    danger_threshold: u16,
     num_dangerous_locs: u32,
 }
 
+enum VentCounts {
+    // The cell at (0, 0) holds the vent count at origin.
+    Dense { counts: Grid<u16>, origin: Loc },
+    Sparse(HashMap<(i64, i64), u16>),
+}
+
 impl LocationGrid {
     pub fn new() -> Self {
         LocationGrid {
-            rows: vec![],
+            vent_counts: VentCounts::Dense { counts: Grid::new(0, 0, 0), origin: Loc::new(0, 0) },
+            danger_threshold: DEFAULT_DANGER_THRESHOLD,
+            num_dangerous_locs: 0,
+        }
+    }
+
+    pub fn sparse() -> Self {
+        LocationGrid {
+            vent_counts: VentCounts::Sparse(HashMap::new()),
+            danger_threshold: DEFAULT_DANGER_THRESHOLD,
             num_dangerous_locs: 0,
         }
     }
 
+    pub fn for_vent_lines(vent_lines: &[LineSegment]) -> Self {
+        if is_sparse(vent_lines) {
+            LocationGrid::sparse()
+        } else {
+            LocationGrid::new()
+        }
+    }
+
+    // Picks the backend for the vent lines and adds them all, including 45 degree diagonals.
+    pub fn from_vent_lines(vent_lines: Vec<LineSegment>) -> Self {
+        let mut loc_grid = LocationGrid::for_vent_lines(&vent_lines);
+        vent_lines.into_iter()
+            .for_each(|vent_line| loc_grid.add_vent_line(vent_line));
+        loc_grid
+    }
+
+    // num_dangerous_locs counts locs with at least this many vents. Cheapest before adding
+    // vents; afterwards the count is recomputed from every loc.
+    pub fn with_danger_threshold(mut self, danger_threshold: u16) -> Self {
+        assert!(danger_threshold > 0, "Danger threshold must be at least 1");
+        self.danger_threshold = danger_threshold;
+        self.num_dangerous_locs = self.vent_locs()
+            .filter(|&(_loc, count)| count >= danger_threshold)
+            .count() as u32;
+        self
+    }
+
+    #[cfg(test)]
+    fn is_sparse(&self) -> bool {
+        matches!(self.vent_counts, VentCounts::Sparse(_))
+    }
+
     pub fn add_vent_line(&mut self, vent_line: LineSegment) {
         if vent_line.is_diagonal() {
-            let locs = vent_line.diagonal_line_segment_locs();
-            locs.for_each(|loc| self.add_vent(&loc));
+            self.reserve(&vent_line.0, &vent_line.1);
+            match vent_line.diagonal_line_segment_locs() {
+                Some(locs) => locs.for_each(|loc| self.add_vent(&loc)),
+                None => self.add_rasterised_vent_line(vent_line, DEFAULT_RASTERISATION),
+            }
         } else {
             self.add_non_diagonal_vent_line(vent_line);
         }
     }
 
+    pub fn add_rasterised_vent_line(&mut self, vent_line: LineSegment, rasterisation: Rasterisation) {
+        self.reserve(&vent_line.0, &vent_line.1);
+        vent_line.rasterised_locs(rasterisation).iter()
+            .for_each(|loc| self.add_vent(loc));
+    }
+
     pub fn add_non_diagonal_vent_line(&mut self, vent_line: LineSegment) {
         if vent_line.is_horizontal() {
+            self.reserve(&vent_line.0, &vent_line.1);
             let locs = vent_line.horizontal_line_segment_locs();
             locs.for_each(|loc| self.add_vent(&loc));
         } else if vent_line.is_vertical() {
+            self.reserve(&vent_line.0, &vent_line.1);
             let locs = vent_line.vertical_line_segment_locs();
             locs.for_each(|loc| self.add_vent(&loc));
         }
     }
 
+    // Grows a dense grid once to hold the box between two vent locs, rather than a row or
+    // column at a time as the vents are added.
+    fn reserve(&mut self, a: &Loc, b: &Loc) {
+        if let VentCounts::Dense { counts, origin } = &mut self.vent_counts {
+            let min = Loc::new(a.x.min(b.x), a.y.min(b.y));
+            let max = Loc::new(a.x.max(b.x), a.y.max(b.y));
+            expand_grid_if_needed(counts, origin, &min, &max);
+        }
+    }
+
     fn add_vent(&mut self, loc: &Loc) {
-        let row_index = loc.y as usize;
-        let col_index = loc.x as usize;
-        self.expand_grid_if_needed(row_index, col_index);
-        self.increment_vent_count(row_index, col_index)
+        self.add_vents(loc, 1);
     }
 
-    fn expand_grid_if_needed(&mut self, row_index: usize, col_index: usize) {
-        if self.rows.len() < row_index + 1 {
-            self.rows.resize(row_index + 1, vec![]);
-        }
-        let row = &mut self.rows[row_index];
-        if row.len() < col_index + 1 {
-            row.resize(col_index + 1, 0);
+    fn add_vents(&mut self, loc: &Loc, num_vents: u16) {
+        let cell = match &mut self.vent_counts {
+            VentCounts::Dense { counts, origin } => {
+                expand_grid_if_needed(counts, origin, loc, loc);
+                &mut counts[((loc.x - origin.x) as usize, (loc.y - origin.y) as usize)]
+            }
+            VentCounts::Sparse(counts) => counts.entry((loc.x, loc.y)).or_insert(0),
+        };
+        // Counts saturate rather than wrap, so a saturated loc stays dangerous.
+        let previous_count = *cell;
+        *cell = previous_count.saturating_add(num_vents);
+        if previous_count < self.danger_threshold && *cell >= self.danger_threshold {
+            self.num_dangerous_locs += 1;
         }
     }
 
-    fn increment_vent_count(&mut self, row_index: usize, col_index: usize) {
-        let cell = &mut self.rows[row_index][col_index];
-        *cell += 1;
-        if *cell == 2 {
-            self.num_dangerous_locs += 1;
+    // Adds every vent in the other grid to this one, as if its vent lines had been added here.
+    pub fn merge(&mut self, other: &LocationGrid) {
+        if let Some((min, max)) = other.bounds() {
+            self.reserve(&min, &max);
+        }
+        for (loc, count) in other.vent_locs() {
+            self.add_vents(&loc, count);
         }
     }
 
     pub fn num_dangerous_locs(&self) -> u32 {
         self.num_dangerous_locs
     }
+
+    #[allow(dead_code)]
+    pub fn num_locs_with_at_least(&self, num_vents: u16) -> u64 {
+        self.overlap_histogram()
+            .range(num_vents.max(1)..)
+            .map(|(_count, num_locs)| num_locs)
+            .sum()
+    }
+
+    // Maps each vent count to the number of locs with exactly that many vents.
+    #[allow(dead_code)]
+    pub fn overlap_histogram(&self) -> BTreeMap<u16, u64> {
+        let mut histogram = BTreeMap::new();
+        for (_loc, count) in self.vent_locs() {
+            *histogram.entry(count).or_insert(0) += 1;
+        }
+        histogram
+    }
+
+    // The highest vent count and every loc with it, in row order.
+    #[allow(dead_code)]
+    pub fn most_dangerous_locs(&self) -> Option<(u16, Vec<Loc>)> {
+        let max_count = self.vent_locs().map(|(_loc, count)| count).max()?;
+        let locs = self.vent_locs()
+            .filter(|&(_loc, count)| count == max_count)
+            .map(|(loc, _count)| loc)
+            .sorted_by_key(|loc| (loc.y, loc.x))
+            .collect_vec();
+        Some((max_count, locs))
+    }
+
+    fn vent_locs(&self) -> Box<dyn Iterator<Item=(Loc, u16)> + '_> {
+        match &self.vent_counts {
+            VentCounts::Dense { counts, origin } => Box::new(
+                counts.positions().zip(counts.cells())
+                    .filter(|(_position, &count)| count > 0)
+                    .map(move |((x, y), &count)| (Loc::new(origin.x + x as i64, origin.y + y as i64), count))),
+            VentCounts::Sparse(counts) => Box::new(
+                counts.iter().map(|(&(x, y), &count)| (Loc::new(x, y), count))),
+        }
+    }
+
+    pub fn vent_count(&self, loc: &Loc) -> u16 {
+        match &self.vent_counts {
+            VentCounts::Dense { counts, origin } => {
+                if loc.x < origin.x || loc.y < origin.y {
+                    return 0;
+                }
+                counts.get((loc.x - origin.x) as usize, (loc.y - origin.y) as usize)
+                    .copied()
+                    .unwrap_or(0)
+            }
+            VentCounts::Sparse(counts) => counts.get(&(loc.x, loc.y)).copied().unwrap_or(0),
+        }
+    }
+
+    // The smallest and largest corners of the box holding every vent, if there are any.
+    pub fn bounds(&self) -> Option<(Loc, Loc)> {
+        match &self.vent_counts {
+            VentCounts::Dense { counts, origin } => {
+                if counts.is_empty() {
+                    return None;
+                }
+                Some((*origin, Loc::new(origin.x + counts.width() as i64 - 1, origin.y + counts.height() as i64 - 1)))
+            }
+            VentCounts::Sparse(counts) => {
+                let (min_x, max_x) = counts.keys().map(|&(x, _y)| x).minmax().into_option()?;
+                let (min_y, max_y) = counts.keys().map(|&(_x, y)| y).minmax().into_option()?;
+                Some((Loc::new(min_x, min_y), Loc::new(max_x, max_y)))
+            }
+        }
+    }
+}
+
+// The grid only ever covers locs that have had vents added, so its corners are the bounds.
+fn expand_grid_if_needed(counts: &mut Grid<u16>, origin: &mut Loc, min: &Loc, max: &Loc) {
+    if counts.is_empty() {
+        *counts = Grid::new((max.x - min.x + 1) as usize, (max.y - min.y + 1) as usize, 0);
+        *origin = *min;
+        return;
+    }
+    let left = (origin.x - min.x).max(0);
+    let top = (origin.y - min.y).max(0);
+    let right = (max.x - (origin.x + counts.width() as i64 - 1)).max(0);
+    let bottom = (max.y - (origin.y + counts.height() as i64 - 1)).max(0);
+    if left + top + right + bottom > 0 {
+        counts.grow(left as usize, top as usize, right as usize, bottom as usize, 0);
+        *origin = Loc::new(origin.x - left, origin.y - top);
+    }
+}
+
+fn is_sparse(vent_lines: &[LineSegment]) -> bool {
+    let (min_x, max_x) = vent_lines.iter()
+        .flat_map(|vent_line| [vent_line.0.x, vent_line.1.x])
+        .minmax()
+        .into_option()
+        .unwrap_or((0, 0));
+    let (min_y, max_y) = vent_lines.iter()
+        .flat_map(|vent_line| [vent_line.0.y, vent_line.1.y])
+        .minmax()
+        .into_option()
+        .unwrap_or((0, 0));
+    let area = (max_x.abs_diff(min_x) + 1).saturating_mul(max_y.abs_diff(min_y) + 1);
+    let total_length: u64 = vent_lines.iter().map(|vent_line| vent_line.num_locs()).sum();
+    area > total_length.saturating_mul(SPARSE_AREA_RATIO)
 }
 
-#[derive(Debug, PartialEq)]
-struct LineSegment(Loc, Loc);
+#[derive(Debug, Clone, PartialEq)]
+pub struct LineSegment(pub Loc, pub Loc);
 
 impl LineSegment {
     pub fn is_horizontal(&self) -> bool {
@@ -114,6 +338,34 @@ impl LineSegment {
         !self.is_vertical() && !self.is_horizontal()
     }
 
+    // True if the two segments share any point, treating them as continuous lines.
+    pub fn intersects(&self, other: &LineSegment) -> bool {
+        let orientations = [
+            orientation(&self.0, &self.1, &other.0),
+            orientation(&self.0, &self.1, &other.1),
+            orientation(&other.0, &other.1, &self.0),
+            orientation(&other.0, &other.1, &self.1),
+        ];
+        if orientations[0] * orientations[1] < 0 && orientations[2] * orientations[3] < 0 {
+            return true;
+        }
+        (orientations[0] == 0 && self.bounding_box_contains(&other.0))
+            || (orientations[1] == 0 && self.bounding_box_contains(&other.1))
+            || (orientations[2] == 0 && other.bounding_box_contains(&self.0))
+            || (orientations[3] == 0 && other.bounding_box_contains(&self.1))
+    }
+
+    fn bounding_box_contains(&self, loc: &Loc) -> bool {
+        (self.0.x.min(self.1.x)..=self.0.x.max(self.1.x)).contains(&loc.x)
+            && (self.0.y.min(self.1.y)..=self.0.y.max(self.1.y)).contains(&loc.y)
+    }
+
+    pub fn num_locs(&self) -> u64 {
+        let dx = self.0.x.abs_diff(self.1.x);
+        let dy = self.0.y.abs_diff(self.1.y);
+        dx.max(dy) + 1
+    }
+
     pub fn horizontal_line_segment_locs(&self) -> impl Iterator<Item=Loc> {
         let y = self.0.y;
         from_to(self.0.x, self.1.x)
@@ -126,21 +378,107 @@ impl LineSegment {
             .map(move |y| Loc::new(x, y))
     }
 
-    pub fn diagonal_line_segment_locs(&self) -> impl Iterator<Item=Loc> {
-        from_to(self.0.x, self.1.x)
+    // None unless the line is at 45 degrees.
+    pub fn diagonal_line_segment_locs(&self) -> Option<impl Iterator<Item=Loc>> {
+        if self.0.x.abs_diff(self.1.x) != self.0.y.abs_diff(self.1.y) {
+            return None;
+        }
+        Some(from_to(self.0.x, self.1.x)
             .zip(from_to(self.0.y, self.1.y))
-            .map(|(x, y)| Loc::new(x, y))
+            .map(|(x, y)| Loc::new(x, y)))
     }
+
+    pub fn rasterised_locs(&self, rasterisation: Rasterisation) -> Vec<Loc> {
+        let (x0, y0) = (self.0.x, self.0.y);
+        let (dx, dy) = (self.1.x - x0, self.1.y - y0);
+        let offsets = match rasterisation {
+            Rasterisation::Bresenham => bresenham_offsets(dx, dy),
+            Rasterisation::Supercover => supercover_offsets(dx, dy),
+            Rasterisation::ExactLatticePoints => lattice_point_offsets(dx, dy),
+        };
+        offsets.into_iter()
+            .map(|(x_offset, y_offset)| Loc::new(x0 + x_offset, y0 + y_offset))
+            .collect()
+    }
+}
+
+// Which side of the line through a and b that c lies on: positive, negative, or 0 if on it.
+fn orientation(a: &Loc, b: &Loc, c: &Loc) -> i64 {
+    let cross = (b.x - a.x) as i128 * (c.y - a.y) as i128 - (b.y - a.y) as i128 * (c.x - a.x) as i128;
+    cross.signum() as i64
 }
 
-#[derive(Debug, PartialEq)]
-struct Loc {
-    pub x: u32,
-    pub y: u32,
+fn bresenham_offsets(dx: i64, dy: i64) -> Vec<(i64, i64)> {
+    let (step_x, step_y) = (dx.signum(), dy.signum());
+    let (dx, dy) = (dx.abs(), -dy.abs());
+    let mut error = dx + dy;
+    let (mut x, mut y) = (0, 0);
+    let mut offsets = vec![(x, y)];
+    while (x, y) != (dx * step_x, -dy * step_y) {
+        let doubled_error = 2 * error;
+        if doubled_error >= dy {
+            error += dy;
+            x += step_x;
+        }
+        if doubled_error <= dx {
+            error += dx;
+            y += step_y;
+        }
+        offsets.push((x, y));
+    }
+    offsets
+}
+
+fn supercover_offsets(dx: i64, dy: i64) -> Vec<(i64, i64)> {
+    let (step_x, step_y) = (dx.signum(), dy.signum());
+    let (num_x_steps, num_y_steps) = (dx.abs(), dy.abs());
+    let (mut x_steps, mut y_steps) = (0, 0);
+    let (mut x, mut y) = (0, 0);
+    let mut offsets = vec![(x, y)];
+    while x_steps < num_x_steps || y_steps < num_y_steps {
+        // Compares where the line leaves the current cell: through its side, its top or bottom, or a corner.
+        let decision = (1 + 2 * x_steps) * num_y_steps - (1 + 2 * y_steps) * num_x_steps;
+        if decision == 0 {
+            offsets.push((x + step_x, y));
+            offsets.push((x, y + step_y));
+            x += step_x;
+            y += step_y;
+            x_steps += 1;
+            y_steps += 1;
+        } else if decision < 0 {
+            x += step_x;
+            x_steps += 1;
+        } else {
+            y += step_y;
+            y_steps += 1;
+        }
+        offsets.push((x, y));
+    }
+    offsets
+}
+
+fn lattice_point_offsets(dx: i64, dy: i64) -> Vec<(i64, i64)> {
+    let num_steps = gcd(dx.abs(), dy.abs());
+    if num_steps == 0 {
+        return vec![(0, 0)];
+    }
+    (0..=num_steps)
+        .map(|step| (step * dx / num_steps, step * dy / num_steps))
+        .collect()
+}
+
+pub fn gcd(a: i64, b: i64) -> i64 {
+    if b == 0 { a } else { gcd(b, a % b) }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Loc {
+    pub x: i64,
+    pub y: i64,
 }
 
 impl Loc {
-    pub fn new(x: u32, y: u32) -> Self {
+    pub fn new(x: i64, y: i64) -> Self {
         Loc { x, y }
     }
 }
@@ -153,7 +491,7 @@ mod tests {
     #[test]
     fn parse_example_input_line() {
         assert_eq!(
-            parse_input_line("957,596 -> 35,182"),
+            parse_input_line("957,596 -> 35,182", 1).unwrap(),
             LineSegment(Loc::new(957, 596), Loc::new(35, 182))
         );
     }
@@ -250,6 +588,246 @@ mod tests {
         assert_eq!(loc_grid.num_dangerous_locs(), 1);
     }
 
+    #[test]
+    fn sparse_location_grid_counts_same_as_dense() {
+        let mut loc_grid = LocationGrid::sparse();
+        loc_grid.add_vent_line(LineSegment(Loc::new(5, 5), Loc::new(0, 0)));
+        loc_grid.add_vent_line(LineSegment(Loc::new(0, 2), Loc::new(5, 2)));
+        loc_grid.add_vent_line(LineSegment(Loc::new(2, 0), Loc::new(2, 5)));
+        assert_eq!(loc_grid.num_dangerous_locs(), 1);
+    }
+
+    #[test]
+    fn sparse_location_grid_handles_huge_coordinates() {
+        let mut loc_grid = LocationGrid::sparse();
+        loc_grid.add_vent_line(LineSegment(Loc::new(4_000_000_000, 3_999_999_993), Loc::new(4_000_000_003, 3_999_999_993)));
+        loc_grid.add_vent_line(LineSegment(Loc::new(4_000_000_001, 4_000_000_000), Loc::new(4_000_000_001, 3_999_999_990)));
+        assert_eq!(loc_grid.num_dangerous_locs(), 1);
+    }
+
+    #[test]
+    fn location_grid_picks_sparse_for_far_apart_lines() {
+        let vent_lines = vec![
+            LineSegment(Loc::new(0, 0), Loc::new(3, 0)),
+            LineSegment(Loc::new(4_000_000_000, 0), Loc::new(4_000_000_000, 3)),
+        ];
+        assert!(LocationGrid::for_vent_lines(&vent_lines).is_sparse());
+    }
+
+    #[test]
+    fn location_grid_picks_dense_for_compact_lines() {
+        let vent_lines = vec![
+            LineSegment(Loc::new(0, 9), Loc::new(5, 9)),
+            LineSegment(Loc::new(8, 0), Loc::new(0, 8)),
+        ];
+        assert!(!LocationGrid::for_vent_lines(&vent_lines).is_sparse());
+    }
+
+    #[test]
+    fn huge_coordinates_input_5b() {
+        let input = to_string_iter(vec![
+            "4000000000,0 -> 4000000000,9",
+            "3999999995,5 -> 4000000005,5",
+        ]);
+        assert_eq!(day5b(input), ("day5b", 1));
+    }
+
+    #[test]
+    fn day5b_rasterises_other_slopes_by_default() {
+        let input = vec![
+            "0,0 -> 4,2",
+            "1,0 -> 1,4",
+            "2,0 -> 2,4",
+        ];
+        // Bresenham crosses the vertical lines at (1, 1) and (2, 1).
+        assert_eq!(day5b(to_string_iter(input.clone())), ("day5b", 2));
+        assert_eq!(day5b_with_rasterisation(to_string_iter(input), DEFAULT_RASTERISATION), ("day5b", 2));
+        assert_eq!(LineSegment(Loc::new(0, 0), Loc::new(4, 2)).diagonal_line_segment_locs().map(|locs| locs.count()), None);
+    }
+
+    #[test]
+    fn bresenham_rasterises_shallow_line() {
+        assert_eq!(LineSegment(Loc::new(0, 0), Loc::new(4, 2)).rasterised_locs(Rasterisation::Bresenham),
+                   vec![Loc::new(0, 0), Loc::new(1, 1), Loc::new(2, 1), Loc::new(3, 2), Loc::new(4, 2)]);
+    }
+
+    #[test]
+    fn bresenham_rasterises_steep_reversed_line() {
+        assert_eq!(LineSegment(Loc::new(1, 3), Loc::new(0, 0)).rasterised_locs(Rasterisation::Bresenham),
+                   vec![Loc::new(1, 3), Loc::new(1, 2), Loc::new(0, 1), Loc::new(0, 0)]);
+    }
+
+    #[test]
+    fn supercover_includes_both_cells_at_corners() {
+        assert_eq!(LineSegment(Loc::new(2, 0), Loc::new(0, 2)).rasterised_locs(Rasterisation::Supercover),
+                   vec![Loc::new(2, 0), Loc::new(1, 0), Loc::new(2, 1), Loc::new(1, 1),
+                        Loc::new(0, 1), Loc::new(1, 2), Loc::new(0, 2)]);
+    }
+
+    #[test]
+    fn supercover_rasterises_shallow_line_through_corner() {
+        assert_eq!(LineSegment(Loc::new(0, 0), Loc::new(3, 1)).rasterised_locs(Rasterisation::Supercover),
+                   vec![Loc::new(0, 0), Loc::new(1, 0), Loc::new(2, 0), Loc::new(1, 1), Loc::new(2, 1), Loc::new(3, 1)]);
+    }
+
+    #[test]
+    fn exact_lattice_points_skips_cells_between_them() {
+        assert_eq!(LineSegment(Loc::new(0, 0), Loc::new(4, 2)).rasterised_locs(Rasterisation::ExactLatticePoints),
+                   vec![Loc::new(0, 0), Loc::new(2, 1), Loc::new(4, 2)]);
+        assert_eq!(LineSegment(Loc::new(3, 3), Loc::new(3, 3)).rasterised_locs(Rasterisation::ExactLatticePoints),
+                   vec![Loc::new(3, 3)]);
+    }
+
+    #[test]
+    fn rasterisations_agree_on_45_degree_lines() {
+        let vent_line = LineSegment(Loc::new(5, 5), Loc::new(0, 0));
+        let expected = vent_line.diagonal_line_segment_locs().unwrap().collect_vec();
+        assert_eq!(vent_line.rasterised_locs(Rasterisation::Bresenham), expected);
+        assert_eq!(vent_line.rasterised_locs(Rasterisation::ExactLatticePoints), expected);
+    }
+
+    #[test]
+    fn example_input_5b_with_rasterisation() {
+        let input = vec![
+            "0,0 -> 4,2",
+            "1,0 -> 1,4",
+        ];
+        assert_eq!(day5b_with_rasterisation(to_string_iter(input.clone()), Rasterisation::ExactLatticePoints), ("day5b", 0));
+        assert_eq!(day5b_with_rasterisation(to_string_iter(input.clone()), Rasterisation::Bresenham), ("day5b", 1));
+        assert_eq!(day5b_with_rasterisation(to_string_iter(input), Rasterisation::Supercover), ("day5b", 2));
+    }
+
+    #[test]
+    fn parse_negative_coordinates() {
+        assert_eq!(
+            parse_input_line("-3,5 -> 7,-12", 1).unwrap(),
+            LineSegment(Loc::new(-3, 5), Loc::new(7, -12))
+        );
+    }
+
+    #[test]
+    fn location_grid_grows_left_and_up() {
+        let mut loc_grid = LocationGrid::new();
+        loc_grid.add_vent_line(LineSegment(Loc::new(3, 3), Loc::new(5, 3)));
+        loc_grid.add_vent_line(LineSegment(Loc::new(-2, -1), Loc::new(4, -1)));
+        loc_grid.add_vent_line(LineSegment(Loc::new(4, -4), Loc::new(4, 4)));
+        loc_grid.add_vent_line(LineSegment(Loc::new(-2, -3), Loc::new(-2, 0)));
+        assert_eq!(loc_grid.num_dangerous_locs(), 3);
+    }
+
+    #[test]
+    fn location_grid_5b_adds_diagonal_through_origin() {
+        let mut loc_grid = LocationGrid::new();
+        loc_grid.add_vent_line(LineSegment(Loc::new(-3, 3), Loc::new(3, -3)));
+        loc_grid.add_vent_line(LineSegment(Loc::new(-3, -3), Loc::new(3, 3)));
+        loc_grid.add_vent_line(LineSegment(Loc::new(-3, -2), Loc::new(-3, 3)));
+        assert_eq!(loc_grid.num_dangerous_locs(), 2);
+    }
+
+    #[test]
+    fn sparse_location_grid_handles_negative_coordinates() {
+        let mut loc_grid = LocationGrid::sparse();
+        loc_grid.add_vent_line(LineSegment(Loc::new(-4_000_000_000, -7), Loc::new(-3_999_999_990, -7)));
+        loc_grid.add_vent_line(LineSegment(Loc::new(-3_999_999_995, -9), Loc::new(-3_999_999_995, 0)));
+        assert_eq!(loc_grid.num_dangerous_locs(), 1);
+    }
+
+    #[test]
+    fn negative_coordinates_input_5b() {
+        let input = to_string_iter(vec![
+            "-5,-5 -> 0,0",
+            "-5,-2 -> 0,-2",
+            "-2,-6 -> -2,1",
+            "1,-6 -> -6,1",
+        ]);
+        assert_eq!(day5b(input), ("day5b", 3));
+    }
+
+    #[test]
+    fn location_grid_counts_locs_at_danger_threshold() {
+        let mut loc_grid = LocationGrid::new().with_danger_threshold(3);
+        loc_grid.add_vent_line(LineSegment(Loc::new(0, 0), Loc::new(3, 0)));
+        loc_grid.add_vent_line(LineSegment(Loc::new(1, 0), Loc::new(3, 0)));
+        loc_grid.add_vent_line(LineSegment(Loc::new(2, 0), Loc::new(2, 2)));
+        loc_grid.add_vent_line(LineSegment(Loc::new(2, 0), Loc::new(2, 2)));
+        assert_eq!(loc_grid.num_dangerous_locs(), 1);
+        assert_eq!(loc_grid.num_locs_with_at_least(1), 6);
+        assert_eq!(loc_grid.num_locs_with_at_least(2), 5);
+        assert_eq!(loc_grid.num_locs_with_at_least(4), 1);
+    }
+
+    #[test]
+    fn location_grid_overlap_histogram() {
+        let mut loc_grid = LocationGrid::sparse();
+        loc_grid.add_vent_line(LineSegment(Loc::new(0, 0), Loc::new(3, 0)));
+        loc_grid.add_vent_line(LineSegment(Loc::new(1, 0), Loc::new(3, 0)));
+        loc_grid.add_vent_line(LineSegment(Loc::new(2, 0), Loc::new(2, 2)));
+        loc_grid.add_vent_line(LineSegment(Loc::new(2, 0), Loc::new(2, 2)));
+        assert_eq!(loc_grid.overlap_histogram(), BTreeMap::from([(1, 1), (2, 4), (4, 1)]));
+    }
+
+    #[test]
+    fn location_grid_most_dangerous_locs() {
+        let mut loc_grid = LocationGrid::new();
+        assert_eq!(loc_grid.most_dangerous_locs(), None);
+        loc_grid.add_vent_line(LineSegment(Loc::new(-1, 2), Loc::new(3, 2)));
+        loc_grid.add_vent_line(LineSegment(Loc::new(3, 3), Loc::new(-1, -1)));
+        loc_grid.add_vent_line(LineSegment(Loc::new(0, 0), Loc::new(0, 3)));
+        assert_eq!(loc_grid.most_dangerous_locs(), Some((2, vec![Loc::new(0, 0), Loc::new(0, 2), Loc::new(2, 2)])));
+    }
+
+    #[test]
+    fn location_grid_saturates_vent_counts() {
+        let mut loc_grid = LocationGrid::new().with_danger_threshold(u16::MAX);
+        for _line_index in 0..70_000 {
+            loc_grid.add_vent_line(LineSegment(Loc::new(0, 0), Loc::new(0, 0)));
+        }
+        assert_eq!(loc_grid.vent_count(&Loc::new(0, 0)), u16::MAX);
+        assert_eq!(loc_grid.num_dangerous_locs(), 1);
+    }
+
+    #[test]
+    fn danger_threshold_set_after_adding_vents_recounts() {
+        let mut loc_grid = LocationGrid::new();
+        loc_grid.add_vent_line(LineSegment(Loc::new(0, 0), Loc::new(3, 0)));
+        loc_grid.add_vent_line(LineSegment(Loc::new(1, 0), Loc::new(2, 0)));
+        loc_grid.add_vent_line(LineSegment(Loc::new(2, 0), Loc::new(2, 0)));
+        assert_eq!(loc_grid.num_dangerous_locs(), 2);
+        let loc_grid = loc_grid.with_danger_threshold(3);
+        assert_eq!(loc_grid.num_dangerous_locs(), 1);
+        let loc_grid = loc_grid.with_danger_threshold(1);
+        assert_eq!(loc_grid.num_dangerous_locs(), 4);
+    }
+
+    #[test]
+    fn example_input_5b_with_danger_threshold() {
+        let input = to_string_iter(vec![
+            "0,9 -> 5,9",
+            "8,0 -> 0,8",
+            "9,4 -> 3,4",
+            "2,2 -> 2,1",
+            "7,0 -> 7,4",
+            "6,4 -> 2,0",
+            "0,9 -> 2,9",
+            "3,4 -> 1,4",
+            "0,0 -> 8,8",
+            "5,5 -> 8,2",
+        ]);
+        assert_eq!(day5b_with_danger_threshold(input, 3), ("day5b", 2));
+    }
+
+    #[test]
+    fn line_segments_intersect() {
+        let vent_line = LineSegment(Loc::new(0, 0), Loc::new(4, 4));
+        assert!(vent_line.intersects(&LineSegment(Loc::new(0, 4), Loc::new(4, 0))));
+        assert!(vent_line.intersects(&LineSegment(Loc::new(0, 1), Loc::new(1, 0))));
+        assert!(vent_line.intersects(&LineSegment(Loc::new(4, 4), Loc::new(9, 4))));
+        assert!(vent_line.intersects(&LineSegment(Loc::new(3, 3), Loc::new(7, 7))));
+        assert!(!vent_line.intersects(&LineSegment(Loc::new(5, 5), Loc::new(7, 7))));
+        assert!(!vent_line.intersects(&LineSegment(Loc::new(1, 0), Loc::new(4, 3))));
+        assert!(!vent_line.intersects(&LineSegment(Loc::new(0, 3), Loc::new(1, 2))));
+    }
+
     #[test]
     fn simple_input_5a() {
         // . 1
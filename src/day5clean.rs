@@ -0,0 +1,354 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use itertools::Itertools;
+use crate::day5::{gcd, LineSegment, Loc, LocationGrid, read_vent_lines};
+
+#[allow(dead_code)]
+pub fn day5b_with_cleanup(input: impl Iterator<Item=String>, cleanup: Cleanup) -> ((&'static str, i32), CleanupReport) {
+    let cleaned = day5_clean_vent_lines(input, cleanup);
+    let loc_grid = LocationGrid::from_vent_lines(cleaned.vent_lines);
+    (("day5b", loc_grid.num_dangerous_locs() as i32), cleaned.report)
+}
+
+#[allow(dead_code)]
+pub fn day5_clean_vent_lines(input: impl Iterator<Item=String>, cleanup: Cleanup) -> CleanedVentLines {
+    let vent_lines = read_vent_lines(input);
+    clean_vent_lines(vent_lines, cleanup)
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Cleanup {
+    // Only point every line from its smaller end to its larger end.
+    Normalise,
+    // Also drop lines identical to an earlier line.
+    RemoveDuplicates,
+    // Also replace each run of collinear lines that share locs by one line covering them all.
+    MergeOverlaps,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct CleanedVentLines {
+    pub vent_lines: Vec<LineSegment>,
+    pub report: CleanupReport,
+}
+
+// Line indexes refer to the input lines, counting from 0.
+#[derive(Debug, Default, PartialEq)]
+pub struct CleanupReport {
+    pub reversed: Vec<usize>,
+    // Each duplicate line with the first line it repeats.
+    pub duplicates: Vec<(usize, usize)>,
+    // Pairs of collinear lines that share locs without being duplicates.
+    pub overlaps: Vec<(usize, usize)>,
+    // The lines replaced by each merged line.
+    pub merged: Vec<(Vec<usize>, LineSegment)>,
+}
+
+impl fmt::Display for CleanupReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "reversed {} lines", self.reversed.len())?;
+        for (line_index, first_line_index) in &self.duplicates {
+            writeln!(f, "line {} duplicates line {}", line_index, first_line_index)?;
+        }
+        for (line_index1, line_index2) in &self.overlaps {
+            writeln!(f, "lines {} and {} overlap", line_index1, line_index2)?;
+        }
+        for (line_indexes, vent_line) in &self.merged {
+            writeln!(f, "merged lines {} into {},{} -> {},{}", line_indexes.iter().join(", "),
+                     vent_line.0.x, vent_line.0.y, vent_line.1.x, vent_line.1.y)?;
+        }
+        Ok(())
+    }
+}
+
+pub fn clean_vent_lines(vent_lines: Vec<LineSegment>, cleanup: Cleanup) -> CleanedVentLines {
+    let mut report = CleanupReport::default();
+    let vent_lines = vent_lines.into_iter().enumerate()
+        .map(|(line_index, vent_line)| {
+            if (vent_line.1.x, vent_line.1.y) < (vent_line.0.x, vent_line.0.y) {
+                report.reversed.push(line_index);
+                LineSegment(vent_line.1, vent_line.0)
+            } else {
+                vent_line
+            }
+        })
+        .collect_vec();
+
+    let mut first_line_indexes = HashMap::new();
+    for (line_index, vent_line) in vent_lines.iter().enumerate() {
+        let key = (vent_line.0.x, vent_line.0.y, vent_line.1.x, vent_line.1.y);
+        match first_line_indexes.get(&key) {
+            Some(&first_line_index) => report.duplicates.push((line_index, first_line_index)),
+            None => { first_line_indexes.insert(key, line_index); }
+        }
+    }
+    let duplicate_line_indexes: HashSet<usize> = report.duplicates.iter()
+        .map(|&(line_index, _first_line_index)| line_index)
+        .collect();
+    let distinct_line_indexes = (0..vent_lines.len())
+        .filter(|line_index| !duplicate_line_indexes.contains(line_index))
+        .collect_vec();
+
+    let runs = collinear_runs(&vent_lines, &distinct_line_indexes);
+    for run in &runs {
+        report.overlaps.extend(overlapping_pairs(&vent_lines, run));
+    }
+    report.overlaps.sort();
+
+    let cleaned_lines = match cleanup {
+        Cleanup::Normalise => vent_lines,
+        Cleanup::RemoveDuplicates => distinct_line_indexes.iter()
+            .map(|&line_index| vent_lines[line_index].clone())
+            .collect(),
+        Cleanup::MergeOverlaps => {
+            let mut merged_lines: HashMap<usize, LineSegment> = HashMap::new();
+            let mut replaced_line_indexes = HashSet::new();
+            for run in runs.iter().filter(|run| run.len() > 1) {
+                let first = run.iter().min_by_key(|&&line_index| position(&vent_lines[line_index].0)).unwrap();
+                let last = run.iter().max_by_key(|&&line_index| position(&vent_lines[line_index].1)).unwrap();
+                let merged_line = LineSegment(vent_lines[*first].0, vent_lines[*last].1);
+                let line_indexes = run.iter().copied().sorted().collect_vec();
+                merged_lines.insert(line_indexes[0], merged_line.clone());
+                replaced_line_indexes.extend(line_indexes.iter().copied());
+                report.merged.push((line_indexes, merged_line));
+            }
+            report.merged.sort_by_key(|(line_indexes, _merged_line)| line_indexes[0]);
+            distinct_line_indexes.iter()
+                .filter_map(|&line_index| match merged_lines.remove(&line_index) {
+                    Some(merged_line) => Some(merged_line),
+                    None if replaced_line_indexes.contains(&line_index) => None,
+                    None => Some(vent_lines[line_index].clone()),
+                })
+                .collect()
+        }
+    };
+    CleanedVentLines { vent_lines: cleaned_lines, report }
+}
+
+type LineKey = ((i64, i64), i64);
+type Position = (i64, i64);
+
+// Single locs have no direction of their own, so they get this key unless they lie on a longer line.
+const SINGLE_LOC_KEY: LineKey = ((0, 0), 0);
+
+// The smallest whole step along the line, and the line it lies on, for a normalised line.
+fn line_key(vent_line: &LineSegment) -> Option<LineKey> {
+    let (dx, dy) = (vent_line.1.x - vent_line.0.x, vent_line.1.y - vent_line.0.y);
+    let num_steps = gcd(dx.abs(), dy.abs());
+    if num_steps == 0 {
+        return None;
+    }
+    let step = (dx / num_steps, dy / num_steps);
+    Some(key_through(step, &vent_line.0))
+}
+
+fn key_through(step: (i64, i64), loc: &Loc) -> LineKey {
+    (step, step.1 * loc.x - step.0 * loc.y)
+}
+
+// Keys every line. A single loc takes the key of a longer line covering it, so it's compared
+// with that line; if lines of several directions cover it, it's only compared with the first.
+fn line_keys(vent_lines: &[LineSegment], line_indexes: &[usize]) -> Vec<(LineKey, usize)> {
+    let mut keyed_lines = vec![];
+    let mut single_locs = vec![];
+    for &line_index in line_indexes {
+        match line_key(&vent_lines[line_index]) {
+            Some(key) => keyed_lines.push((key, line_index)),
+            None => single_locs.push(line_index),
+        }
+    }
+    if single_locs.is_empty() {
+        return keyed_lines;
+    }
+
+    // Spans sorted by start, each with the furthest end of any span up to it, so whether a
+    // position is covered is one binary search.
+    let mut spans_by_key: HashMap<LineKey, Vec<(Position, Position)>> = HashMap::new();
+    for &(key, line_index) in &keyed_lines {
+        let vent_line = &vent_lines[line_index];
+        spans_by_key.entry(key).or_default().push((position(&vent_line.0), position(&vent_line.1)));
+    }
+    for spans in spans_by_key.values_mut() {
+        spans.sort();
+        let mut furthest_end = (i64::MIN, i64::MIN);
+        for span in spans.iter_mut() {
+            furthest_end = furthest_end.max(span.1);
+            span.1 = furthest_end;
+        }
+    }
+    let steps = spans_by_key.keys().map(|&(step, _offset)| step).unique().sorted().collect_vec();
+    let is_covered = |key: &LineKey, loc: &Loc| spans_by_key.get(key).is_some_and(|spans| {
+        let num_starting_before = spans.partition_point(|&(start, _furthest_end)| start <= position(loc));
+        num_starting_before > 0 && spans[num_starting_before - 1].1 >= position(loc)
+    });
+    for line_index in single_locs {
+        let loc = &vent_lines[line_index].0;
+        let key = steps.iter()
+            .map(|&step| key_through(step, loc))
+            .find(|key| is_covered(key, loc))
+            .unwrap_or(SINGLE_LOC_KEY);
+        keyed_lines.push((key, line_index));
+    }
+    keyed_lines
+}
+
+// Increases along every normalised line, so locs on the same line sort by it.
+fn position(loc: &Loc) -> Position {
+    (loc.x, loc.y)
+}
+
+// Groups collinear lines into runs whose locs join up, each run sorted by start.
+fn collinear_runs(vent_lines: &[LineSegment], line_indexes: &[usize]) -> Vec<Vec<usize>> {
+    let lines_by_key = line_keys(vent_lines, line_indexes).into_iter().into_group_map();
+    let mut runs = vec![];
+    for (_key, line_indexes) in lines_by_key.into_iter().sorted_by_key(|(key, _line_indexes)| *key) {
+        let mut run: Vec<usize> = vec![];
+        let mut run_end = (i64::MIN, i64::MIN);
+        for line_index in line_indexes.into_iter().sorted_by_key(|&line_index| position(&vent_lines[line_index].0)) {
+            let vent_line = &vent_lines[line_index];
+            if !run.is_empty() && position(&vent_line.0) > run_end {
+                runs.push(run);
+                run = vec![];
+            }
+            run.push(line_index);
+            run_end = run_end.max(position(&vent_line.1));
+        }
+        if !run.is_empty() {
+            runs.push(run);
+        }
+    }
+    runs
+}
+
+fn overlapping_pairs(vent_lines: &[LineSegment], run: &[usize]) -> Vec<(usize, usize)> {
+    run.iter()
+        .tuple_combinations()
+        .filter(|(&line_index1, &line_index2)| {
+            let (line1, line2) = (&vent_lines[line_index1], &vent_lines[line_index2]);
+            position(&line1.0).max(position(&line2.0)) <= position(&line1.1).min(position(&line2.1))
+        })
+        .map(|(&line_index1, &line_index2)| (line_index1.min(line_index2), line_index1.max(line_index2)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::day5clean::*;
+    use crate::util::to_string_iter;
+
+    fn survey_input() -> Vec<&'static str> {
+        vec![
+            "5,0 -> 0,0",
+            "0,0 -> 5,0",
+            "3,0 -> 8,0",
+            "2,2 -> 2,6",
+            "0,0 -> 4,4",
+            "6,6 -> 5,5",
+            "7,7 -> 9,9",
+            "2,2 -> 2,6",
+        ]
+    }
+
+    #[test]
+    fn normalise_reverses_lines() {
+        let cleaned = day5_clean_vent_lines(to_string_iter(survey_input()), Cleanup::Normalise);
+        assert_eq!(cleaned.report.reversed, vec![0, 5]);
+        assert_eq!(cleaned.vent_lines[0], LineSegment(Loc::new(0, 0), Loc::new(5, 0)));
+        assert_eq!(cleaned.vent_lines[5], LineSegment(Loc::new(5, 5), Loc::new(6, 6)));
+        assert_eq!(cleaned.vent_lines.len(), 8);
+    }
+
+    #[test]
+    fn report_duplicates_and_overlaps() {
+        let cleaned = day5_clean_vent_lines(to_string_iter(survey_input()), Cleanup::Normalise);
+        assert_eq!(cleaned.report.duplicates, vec![(1, 0), (7, 3)]);
+        assert_eq!(cleaned.report.overlaps, vec![(0, 2)]);
+        assert!(cleaned.report.merged.is_empty());
+    }
+
+    #[test]
+    fn remove_duplicates() {
+        let cleaned = day5_clean_vent_lines(to_string_iter(survey_input()), Cleanup::RemoveDuplicates);
+        assert_eq!(cleaned.vent_lines.len(), 6);
+        assert!(cleaned.report.merged.is_empty());
+    }
+
+    #[test]
+    fn merge_overlaps() {
+        let cleaned = day5_clean_vent_lines(to_string_iter(survey_input()), Cleanup::MergeOverlaps);
+        assert_eq!(cleaned.report.merged, vec![(vec![0, 2], LineSegment(Loc::new(0, 0), Loc::new(8, 0)))]);
+        assert_eq!(cleaned.vent_lines, vec![
+            LineSegment(Loc::new(0, 0), Loc::new(8, 0)),
+            LineSegment(Loc::new(2, 2), Loc::new(2, 6)),
+            LineSegment(Loc::new(0, 0), Loc::new(4, 4)),
+            LineSegment(Loc::new(5, 5), Loc::new(6, 6)),
+            LineSegment(Loc::new(7, 7), Loc::new(9, 9)),
+        ]);
+    }
+
+    #[test]
+    fn lines_sharing_an_end_overlap() {
+        let cleaned = day5_clean_vent_lines(to_string_iter(vec![
+            "0,0 -> 2,2",
+            "4,4 -> 2,2",
+            "0,4 -> 0,0",
+            "0,6 -> 0,5",
+        ]), Cleanup::MergeOverlaps);
+        assert_eq!(cleaned.report.overlaps, vec![(0, 1)]);
+        assert_eq!(cleaned.vent_lines, vec![
+            LineSegment(Loc::new(0, 0), Loc::new(4, 4)),
+            LineSegment(Loc::new(0, 0), Loc::new(0, 4)),
+            LineSegment(Loc::new(0, 5), Loc::new(0, 6)),
+        ]);
+    }
+
+    #[test]
+    fn merge_steep_collinear_lines() {
+        let cleaned = day5_clean_vent_lines(to_string_iter(vec![
+            "0,0 -> 2,4",
+            "3,6 -> 1,2",
+            "1,3 -> 2,5",
+        ]), Cleanup::MergeOverlaps);
+        assert_eq!(cleaned.report.overlaps, vec![(0, 1)]);
+        assert_eq!(cleaned.report.merged, vec![(vec![0, 1], LineSegment(Loc::new(0, 0), Loc::new(3, 6)))]);
+    }
+
+    #[test]
+    fn single_locs_overlap_the_lines_they_lie_on() {
+        let cleaned = day5_clean_vent_lines(to_string_iter(vec![
+            "0,0 -> 4,0",
+            "2,0 -> 2,0",
+            "3,3 -> 3,3",
+            "0,0 -> 4,4",
+            "0,4 -> 4,4",
+            "6,6 -> 6,6",
+            "4,0 -> 4,4",
+            "4,4 -> 4,4",
+        ]), Cleanup::MergeOverlaps);
+        assert_eq!(cleaned.report.overlaps, vec![(0, 1), (2, 3), (6, 7)]);
+        assert_eq!(cleaned.report.merged, vec![
+            (vec![0, 1], LineSegment(Loc::new(0, 0), Loc::new(4, 0))),
+            (vec![2, 3], LineSegment(Loc::new(0, 0), Loc::new(4, 4))),
+            (vec![6, 7], LineSegment(Loc::new(4, 0), Loc::new(4, 4))),
+        ]);
+        assert_eq!(cleaned.vent_lines.len(), 5);
+    }
+
+    #[test]
+    fn cleanup_changes_overlap_count() {
+        assert_eq!(day5b_with_cleanup(to_string_iter(survey_input()), Cleanup::Normalise).0, ("day5b", 11));
+        assert_eq!(day5b_with_cleanup(to_string_iter(survey_input()), Cleanup::MergeOverlaps).0, ("day5b", 2));
+    }
+
+    #[test]
+    fn report_as_text() {
+        let report = day5_clean_vent_lines(to_string_iter(survey_input()), Cleanup::MergeOverlaps).report;
+        assert_eq!(report.to_string(),
+                   "reversed 2 lines\n\
+                    line 1 duplicates line 0\n\
+                    line 7 duplicates line 3\n\
+                    lines 0 and 2 overlap\n\
+                    merged lines 0, 2 into 0,0 -> 8,0\n");
+    }
+}
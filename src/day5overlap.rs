@@ -0,0 +1,348 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+use itertools::Itertools;
+use crate::day5::{gcd, LineSegment, read_vent_lines};
+
+#[allow(dead_code)]
+pub fn day5a_analytic(input: impl Iterator<Item=String>) -> (&'static str, i32) {
+    let vent_lines = read_vent_lines(input).into_iter()
+        .filter(|vent_line| !vent_line.is_diagonal())
+        .collect_vec();
+    ("day5a", count_overlap_points(&vent_lines) as i32)
+}
+
+#[allow(dead_code)]
+pub fn day5b_analytic(input: impl Iterator<Item=String>) -> (&'static str, i32) {
+    let vent_lines = read_vent_lines(input);
+    ("day5b", count_overlap_points(&vent_lines) as i32)
+}
+
+// Counts the distinct lattice points covered by two or more vent lines without visiting every
+// point. Lines may have any slope and cover only the lattice points exactly on them. Collinear
+// lines are merged into overlap intervals with a sweep along their shared line, and crossings
+// between each pair of directions are found with a sweep across both, so only lines that
+// actually cross are compared.
+pub fn count_overlap_points(vent_lines: &[LineSegment]) -> u64 {
+    let segments = vent_lines.iter().map(Segment::new).collect_vec();
+    let overlaps = collinear_overlaps(&segments);
+    let num_overlap_points: u64 = overlaps.values()
+        .flat_map(|intervals| intervals.iter())
+        .map(|&(low, high)| (high - low + 1) as u64)
+        .sum();
+    let overlap_directions = overlaps.keys().map(|&(direction, _line_key)| direction).unique().collect_vec();
+
+    let segments_by_direction = segments.iter().into_group_map_by(|segment| segment.direction);
+    let mut crossing_points = HashSet::new();
+    for (direction1, direction2) in segments_by_direction.keys().tuple_combinations() {
+        crossing_points.extend(sweep_crossings(&segments_by_direction[direction1], &segments_by_direction[direction2]));
+    }
+
+    let mut num_points = num_overlap_points as i64;
+    for &point in &crossing_points {
+        let num_containing_overlaps = overlap_directions.iter()
+            .filter(|direction| overlaps_contain(&overlaps, **direction, point))
+            .count() as i64;
+        num_points += if num_containing_overlaps == 0 { 1 } else { 1 - num_containing_overlaps };
+    }
+    num_points as u64
+}
+
+// The smallest whole step along a line, pointing right, or down for vertical lines.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct Direction {
+    step: (i64, i64),
+}
+
+impl Direction {
+    fn new(dx: i64, dy: i64) -> Self {
+        let num_steps = gcd(dx.abs(), dy.abs());
+        if num_steps == 0 {
+            // A single loc lies on a line in every direction; horizontal will do.
+            return Direction { step: (1, 0) };
+        }
+        let step = (dx / num_steps, dy / num_steps);
+        if step.0 < 0 || (step.0 == 0 && step.1 < 0) {
+            Direction { step: (-step.0, -step.1) }
+        } else {
+            Direction { step }
+        }
+    }
+
+    // The line through (x, y) in this direction is a*x + b*y = key.
+    fn coefficients(&self) -> (i64, i64) {
+        (self.step.1, -self.step.0)
+    }
+
+    fn line_key(&self, (x, y): (i64, i64)) -> i64 {
+        let (a, b) = self.coefficients();
+        a * x + b * y
+    }
+
+    // Position along the line, one step per lattice point.
+    fn position(&self, (x, y): (i64, i64)) -> i64 {
+        if self.step.0 == 0 {
+            y
+        } else {
+            x.div_euclid(self.step.0)
+        }
+    }
+}
+
+struct Segment {
+    direction: Direction,
+    start: (i64, i64),
+    end: (i64, i64),
+}
+
+impl Segment {
+    fn new(vent_line: &LineSegment) -> Self {
+        let start = (vent_line.0.x, vent_line.0.y);
+        let end = (vent_line.1.x, vent_line.1.y);
+        Segment { direction: Direction::new(end.0 - start.0, end.1 - start.1), start, end }
+    }
+
+    fn line_key(&self) -> i64 {
+        self.direction.line_key(self.start)
+    }
+
+    fn interval(&self) -> (i64, i64) {
+        let start = self.direction.position(self.start);
+        let end = self.direction.position(self.end);
+        (start.min(end), start.max(end))
+    }
+
+    // The range of line keys in another direction that this segment passes through.
+    fn key_range(&self, direction: Direction) -> (i64, i64) {
+        let start = direction.line_key(self.start);
+        let end = direction.line_key(self.end);
+        (start.min(end), start.max(end))
+    }
+
+    // Where the lines through the two segments meet, if that is a lattice point.
+    fn lattice_crossing(&self, other: &Segment) -> Option<(i64, i64)> {
+        let (a1, b1) = self.direction.coefficients();
+        let (a2, b2) = other.direction.coefficients();
+        let [a1, b1, a2, b2] = [a1, b1, a2, b2].map(i128::from);
+        let (c1, c2) = (i128::from(self.line_key()), i128::from(other.line_key()));
+        let determinant = a1 * b2 - a2 * b1;
+        let x_numerator = c1 * b2 - c2 * b1;
+        let y_numerator = a1 * c2 - a2 * c1;
+        if x_numerator % determinant != 0 || y_numerator % determinant != 0 {
+            return None;
+        }
+        Some(((x_numerator / determinant) as i64, (y_numerator / determinant) as i64))
+    }
+}
+
+// Measured by the line keys of the two directions, each row segment has one key in the first
+// direction and spans a range in the second, and each column segment the other way round. A
+// sweep across the second direction's keys keeps the rows it is inside ordered by their first
+// key, so each column only meets the rows within its own range.
+fn sweep_crossings(rows: &[&Segment], columns: &[&Segment]) -> Vec<(i64, i64)> {
+    let (row_direction, column_direction) = (rows[0].direction, columns[0].direction);
+    // At the same position, rows start before columns are checked and end after them.
+    let mut events = vec![];
+    for (row_index, row) in rows.iter().enumerate() {
+        let (low, high) = row.key_range(column_direction);
+        events.push((low, 0, row_index));
+        events.push((high, 2, row_index));
+    }
+    for (column_index, column) in columns.iter().enumerate() {
+        events.push((column.line_key(), 1, column_index));
+    }
+    events.sort_unstable();
+
+    let mut active_rows = BTreeSet::new();
+    let mut crossing_points = vec![];
+    for (_position, event, index) in events {
+        match event {
+            0 => { active_rows.insert((rows[index].line_key(), index)); }
+            2 => { active_rows.remove(&(rows[index].line_key(), index)); }
+            _ => {
+                let column = columns[index];
+                let (low, high) = column.key_range(row_direction);
+                crossing_points.extend(active_rows.range((low, 0)..=(high, usize::MAX))
+                    .filter_map(|&(_row_key, row_index)| rows[row_index].lattice_crossing(column)));
+            }
+        }
+    }
+    crossing_points
+}
+
+type Overlaps = HashMap<(Direction, i64), Vec<(i64, i64)>>;
+
+fn collinear_overlaps(segments: &[Segment]) -> Overlaps {
+    segments.iter()
+        .map(|segment| ((segment.direction, segment.line_key()), segment.interval()))
+        .into_group_map()
+        .into_iter()
+        .map(|(line, intervals)| (line, intervals_covered_twice(&intervals)))
+        .filter(|(_line, overlap_intervals)| !overlap_intervals.is_empty())
+        .collect()
+}
+
+fn intervals_covered_twice(intervals: &[(i64, i64)]) -> Vec<(i64, i64)> {
+    let coverage_changes = intervals.iter()
+        .flat_map(|&(low, high)| [(low, 1), (high + 1, -1)])
+        .into_grouping_map()
+        .sum()
+        .into_iter()
+        .sorted()
+        .collect_vec();
+    let mut overlap_intervals = vec![];
+    let mut coverage = 0;
+    let mut overlap_start = None;
+    for (position, change) in coverage_changes {
+        coverage += change;
+        if coverage >= 2 && overlap_start.is_none() {
+            overlap_start = Some(position);
+        } else if coverage < 2 {
+            if let Some(start) = overlap_start.take() {
+                if position > start {
+                    overlap_intervals.push((start, position - 1));
+                }
+            }
+        }
+    }
+    overlap_intervals
+}
+
+fn overlaps_contain(overlaps: &Overlaps, direction: Direction, point: (i64, i64)) -> bool {
+    let position = direction.position(point);
+    overlaps.get(&(direction, direction.line_key(point)))
+        .is_some_and(|intervals| {
+            let index = intervals.partition_point(|&(_low, high)| high < position);
+            index < intervals.len() && intervals[index].0 <= position
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::day5::{day5a, day5b, LocationGrid, parse_input_line, Rasterisation};
+    use crate::day5overlap::*;
+    use crate::util::{Rng, to_string_iter};
+
+    fn example_input() -> Vec<&'static str> {
+        vec![
+            "0,9 -> 5,9",
+            "8,0 -> 0,8",
+            "9,4 -> 3,4",
+            "2,2 -> 2,1",
+            "7,0 -> 7,4",
+            "6,4 -> 2,0",
+            "0,9 -> 2,9",
+            "3,4 -> 1,4",
+            "0,0 -> 8,8",
+            "5,5 -> 8,2",
+        ]
+    }
+
+    #[test]
+    fn example_input_5a() {
+        assert_eq!(day5a_analytic(to_string_iter(example_input())), ("day5a", 5));
+    }
+
+    #[test]
+    fn example_input_5b() {
+        assert_eq!(day5b_analytic(to_string_iter(example_input())), ("day5b", 12));
+    }
+
+    #[test]
+    fn intervals_covered_twice_merges_touching_overlaps() {
+        assert_eq!(intervals_covered_twice(&[(0, 5), (3, 9), (6, 7), (12, 12), (12, 12)]),
+                   vec![(3, 7), (12, 12)]);
+    }
+
+    #[test]
+    fn diagonals_crossing_between_lattice_points_do_not_overlap() {
+        let input = to_string_iter(vec![
+            "0,0 -> 1,1",
+            "0,1 -> 1,0",
+        ]);
+        assert_eq!(day5b_analytic(input), ("day5b", 0));
+    }
+
+    #[test]
+    fn crossing_inside_two_collinear_overlaps_counts_once() {
+        let input = to_string_iter(vec![
+            "0,2 -> 4,2",
+            "1,2 -> 3,2",
+            "2,0 -> 2,4",
+            "2,1 -> 2,3",
+        ]);
+        assert_eq!(day5a_analytic(input), ("day5a", 5));
+    }
+
+    #[test]
+    fn long_lines_are_not_rasterised() {
+        let input = to_string_iter(vec![
+            "0,0 -> 4000000000,4000000000",
+            "0,4000000000 -> 4000000000,0",
+            "2000000000,0 -> 2000000000,4000000000",
+        ]);
+        assert_eq!(day5b_analytic(input), ("day5b", 1));
+    }
+
+    #[test]
+    fn lines_of_any_slope_cover_only_their_lattice_points() {
+        let input = to_string_iter(vec![
+            "0,0 -> 4,2",
+            "2,0 -> 2,4",
+            "0,3 -> 6,0",
+            "4,0 -> 4,2",
+            "3,0 -> 1,4",
+        ]);
+        // (2,1), (4,1) and (4,2) lie on two lines and (2,2) on three; the crossings of the
+        // first line with the third and the last fall between lattice points.
+        assert_eq!(day5b_analytic(input), ("day5b", 4));
+    }
+
+    #[test]
+    fn collinear_steep_lines_overlap_by_lattice_points() {
+        let input = to_string_iter(vec![
+            "0,0 -> 3,9",
+            "6,18 -> 1,3",
+        ]);
+        assert_eq!(day5b_analytic(input), ("day5b", 3));
+    }
+
+    #[test]
+    fn matches_rasterised_counts_on_random_input() {
+        let mut rng = Rng::new(5);
+        for _trial in 0..50 {
+            let lines = (0..30)
+                .map(|_line_index| {
+                    let x1 = rng.below(20);
+                    let y1 = 10 + rng.below(20);
+                    let length = rng.below(10);
+                    let (x2, y2) = match rng.below(4) {
+                        0 => (x1 + length, y1),
+                        1 => (x1, y1 + length),
+                        2 => (x1 + length, y1 + length),
+                        _ => (x1 + length, y1 - length),
+                    };
+                    format!("{},{} -> {},{}", x1, y1, x2, y2)
+                })
+                .collect_vec();
+            assert_eq!(day5a_analytic(lines.iter().cloned()), day5a(lines.iter().cloned()));
+            assert_eq!(day5b_analytic(lines.iter().cloned()), day5b(lines.iter().cloned()));
+        }
+    }
+
+    #[test]
+    fn matches_exact_lattice_points_on_random_input_of_any_slope() {
+        let mut rng = Rng::new(41);
+        for _trial in 0..50 {
+            let lines = (0..30)
+                .map(|_line_index| {
+                    let [x1, y1, x2, y2] = [0; 4].map(|_| rng.below(25));
+                    format!("{},{} -> {},{}", x1, y1, x2, y2)
+                })
+                .collect_vec();
+            let mut loc_grid = LocationGrid::new();
+            for line in lines.iter() {
+                loc_grid.add_rasterised_vent_line(parse_input_line(line, 1).unwrap(), Rasterisation::ExactLatticePoints);
+            }
+            assert_eq!(day5b_analytic(lines.iter().cloned()), ("day5b", loc_grid.num_dangerous_locs() as i32));
+        }
+    }
+}
@@ -0,0 +1,121 @@
+use std::thread;
+use itertools::Itertools;
+use crate::day5::{LineSegment, LocationGrid, read_vent_lines};
+
+#[allow(dead_code)]
+pub fn day5a_parallel(input: impl Iterator<Item=String>, num_threads: usize) -> (&'static str, i32) {
+    let vent_lines = read_vent_lines(input);
+    let loc_grid = fill_grid_parallel(&vent_lines, num_threads, LocationGrid::add_non_diagonal_vent_line);
+    ("day5a", loc_grid.num_dangerous_locs() as i32)
+}
+
+#[allow(dead_code)]
+pub fn day5b_parallel(input: impl Iterator<Item=String>, num_threads: usize) -> (&'static str, i32) {
+    let vent_lines = read_vent_lines(input);
+    let loc_grid = fill_grid_parallel(&vent_lines, num_threads, LocationGrid::add_vent_line);
+    ("day5b", loc_grid.num_dangerous_locs() as i32)
+}
+
+// Each thread adds a contiguous share of the vent lines to its own grid, and the grids are
+// merged in thread order so that the result doesn't depend on scheduling.
+pub fn fill_grid_parallel(vent_lines: &[LineSegment], num_threads: usize,
+                          add_vent_line: fn(&mut LocationGrid, LineSegment)) -> LocationGrid {
+    assert!(num_threads > 0, "Need at least one thread");
+    let lines_per_thread = vent_lines.len().div_ceil(num_threads).max(1);
+    let thread_grids = thread::scope(|scope| {
+        let handles = vent_lines.chunks(lines_per_thread)
+            .map(|thread_lines| scope.spawn(move || {
+                let mut loc_grid = LocationGrid::for_vent_lines(vent_lines);
+                thread_lines.iter()
+                    .for_each(|vent_line| add_vent_line(&mut loc_grid, vent_line.clone()));
+                loc_grid
+            }))
+            .collect_vec();
+        handles.into_iter()
+            .map(|handle| handle.join().unwrap())
+            .collect_vec()
+    });
+
+    let mut loc_grid = LocationGrid::for_vent_lines(vent_lines);
+    thread_grids.iter()
+        .for_each(|thread_grid| loc_grid.merge(thread_grid));
+    loc_grid
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::day5::{day5a, day5b, Loc};
+    use crate::day5parallel::*;
+    use crate::util::{Rng, to_string_iter};
+
+    fn random_input(num_lines: usize, seed: u64) -> Vec<String> {
+        let mut rng = Rng::new(seed);
+        (0..num_lines)
+            .map(|_line_index| {
+                let x1 = rng.below(50) as i64 - 25;
+                let y1 = rng.below(50) as i64 - 25;
+                let length = rng.below(20) as i64;
+                let (x2, y2) = match rng.below(4) {
+                    0 => (x1 + length, y1),
+                    1 => (x1, y1 - length),
+                    2 => (x1 - length, y1 + length),
+                    _ => (x1 + length, y1 + length),
+                };
+                format!("{},{} -> {},{}", x1, y1, x2, y2)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn example_input_in_parallel() {
+        let input = vec![
+            "0,9 -> 5,9",
+            "8,0 -> 0,8",
+            "9,4 -> 3,4",
+            "2,2 -> 2,1",
+            "7,0 -> 7,4",
+            "6,4 -> 2,0",
+            "0,9 -> 2,9",
+            "3,4 -> 1,4",
+            "0,0 -> 8,8",
+            "5,5 -> 8,2",
+        ];
+        assert_eq!(day5a_parallel(to_string_iter(input.clone()), 3), ("day5a", 5));
+        assert_eq!(day5b_parallel(to_string_iter(input), 3), ("day5b", 12));
+    }
+
+    #[test]
+    fn parallel_matches_serial_for_any_thread_count() {
+        let input = random_input(500, 11);
+        let serial_5a = day5a(input.iter().cloned());
+        let serial_5b = day5b(input.iter().cloned());
+        for num_threads in [1, 2, 3, 8, 600] {
+            assert_eq!(day5a_parallel(input.iter().cloned(), num_threads), serial_5a);
+            assert_eq!(day5b_parallel(input.iter().cloned(), num_threads), serial_5b);
+        }
+    }
+
+    #[test]
+    fn parallel_grid_has_same_counts_as_serial() {
+        let vent_lines = read_vent_lines(random_input(300, 12).into_iter());
+        let serial_grid = LocationGrid::from_vent_lines(vent_lines.clone());
+        let parallel_grid = fill_grid_parallel(&vent_lines, 4, LocationGrid::add_vent_line);
+        assert_eq!(parallel_grid.bounds(), serial_grid.bounds());
+        assert_eq!(parallel_grid.overlap_histogram(), serial_grid.overlap_histogram());
+        let (min, max) = serial_grid.bounds().unwrap();
+        for (x, y) in (min.x..=max.x).cartesian_product(min.y..=max.y) {
+            assert_eq!(parallel_grid.vent_count(&Loc::new(x, y)), serial_grid.vent_count(&Loc::new(x, y)));
+        }
+    }
+
+    #[test]
+    fn parallel_with_no_vent_lines() {
+        assert_eq!(day5b_parallel(to_string_iter(vec![]), 4), ("day5b", 0));
+    }
+
+    #[test]
+    #[should_panic(expected = "Need at least one thread")]
+    fn parallel_needs_a_thread() {
+        day5b_parallel(to_string_iter(vec!["0,0 -> 1,1"]), 0);
+    }
+}
@@ -0,0 +1,275 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::fmt;
+use itertools::Itertools;
+use crate::day5::{LocationGrid, Loc, read_vent_lines};
+
+#[allow(dead_code)]
+pub fn day5_safe_path(input: impl Iterator<Item=String>, start: Loc, goal: Loc, rules: &PathRules) -> Result<SafePath, NoPath> {
+    let loc_grid = LocationGrid::from_vent_lines(read_vent_lines(input));
+    find_safe_path(&loc_grid, start, goal, rules)
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Moves {
+    FourConnected,
+    EightConnected,
+}
+
+// Entering a loc costs step_cost plus vent_cost for each vent there. Locs with at least
+// impassable_at vents can't be entered at all.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PathRules {
+    pub moves: Moves,
+    pub step_cost: u64,
+    pub vent_cost: u64,
+    pub impassable_at: Option<u16>,
+}
+
+#[allow(dead_code)]
+impl PathRules {
+    pub fn new(moves: Moves) -> Self {
+        PathRules {
+            moves,
+            step_cost: 1,
+            vent_cost: 10,
+            impassable_at: Some(2),
+        }
+    }
+
+    fn entry_cost(&self, num_vents: u16) -> Option<u64> {
+        if self.impassable_at.is_some_and(|impassable_at| num_vents >= impassable_at) {
+            return None;
+        }
+        // Costs saturate, so huge configured costs make a loc very expensive rather than overflowing.
+        Some(self.step_cost.saturating_add(self.vent_cost.saturating_mul(num_vents as u64)))
+    }
+
+    fn neighbours(&self, loc: &Loc) -> Vec<Loc> {
+        let offsets: &[(i64, i64)] = match self.moves {
+            Moves::FourConnected => &[(0, -1), (-1, 0), (1, 0), (0, 1)],
+            Moves::EightConnected => &[(-1, -1), (0, -1), (1, -1), (-1, 0), (1, 0), (-1, 1), (0, 1), (1, 1)],
+        };
+        offsets.iter()
+            .map(|(dx, dy)| Loc::new(loc.x + dx, loc.y + dy))
+            .collect()
+    }
+
+    // Never more than the real cost, since every move costs at least step_cost.
+    fn estimated_cost(&self, from: &Loc, to: &Loc) -> u64 {
+        let (dx, dy) = (from.x.abs_diff(to.x), from.y.abs_diff(to.y));
+        let num_moves = match self.moves {
+            Moves::FourConnected => dx + dy,
+            Moves::EightConnected => dx.max(dy),
+        };
+        num_moves.saturating_mul(self.step_cost)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct SafePath {
+    pub locs: Vec<Loc>,
+    pub cost: u64,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum NoPath {
+    StartImpassable { num_vents: u16 },
+    GoalImpassable { num_vents: u16 },
+    Unreachable { num_locs_explored: usize },
+}
+
+impl fmt::Display for NoPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NoPath::StartImpassable { num_vents } =>
+                write!(f, "start has {} vents, which makes it impassable", num_vents),
+            NoPath::GoalImpassable { num_vents } =>
+                write!(f, "goal has {} vents, which makes it impassable", num_vents),
+            NoPath::Unreachable { num_locs_explored } =>
+                write!(f, "goal is walled off by impassable locs ({} locs reachable from start)", num_locs_explored),
+        }
+    }
+}
+
+// A* search. Locs outside the vent field are all alike, so the search stays within one loc
+// of the box holding the vents, the start and the goal. Of the locs with the same estimate,
+// the one furthest along is taken first, so open ground is crossed in a straight run rather
+// than by filling the box between start and goal. Paths whose cost would overflow are dropped.
+pub fn find_safe_path(loc_grid: &LocationGrid, start: Loc, goal: Loc, rules: &PathRules) -> Result<SafePath, NoPath> {
+    let start_vents = loc_grid.vent_count(&start);
+    if rules.entry_cost(start_vents).is_none() {
+        return Err(NoPath::StartImpassable { num_vents: start_vents });
+    }
+    let goal_vents = loc_grid.vent_count(&goal);
+    if rules.entry_cost(goal_vents).is_none() {
+        return Err(NoPath::GoalImpassable { num_vents: goal_vents });
+    }
+    let (min, max) = search_window(loc_grid, &start, &goal);
+
+    let mut best_costs = HashMap::from([((start.x, start.y), 0)]);
+    let mut came_from = HashMap::new();
+    let mut frontier = BinaryHeap::from([Reverse((rules.estimated_cost(&start, &goal), Reverse(0), start.y, start.x))]);
+    while let Some(Reverse((_estimate, Reverse(cost), y, x))) = frontier.pop() {
+        let loc = Loc::new(x, y);
+        if loc == goal {
+            return Ok(SafePath { locs: trace_path(&came_from, goal), cost });
+        }
+        if best_costs[&(x, y)] < cost {
+            continue;
+        }
+        for neighbour in rules.neighbours(&loc) {
+            if !(min.x..=max.x).contains(&neighbour.x) || !(min.y..=max.y).contains(&neighbour.y) {
+                continue;
+            }
+            let entry_cost = match rules.entry_cost(loc_grid.vent_count(&neighbour)) {
+                Some(entry_cost) => entry_cost,
+                None => continue,
+            };
+            let neighbour_cost = match cost.checked_add(entry_cost) {
+                Some(neighbour_cost) => neighbour_cost,
+                None => continue,
+            };
+            let key = (neighbour.x, neighbour.y);
+            if best_costs.get(&key).is_none_or(|&best_cost| neighbour_cost < best_cost) {
+                best_costs.insert(key, neighbour_cost);
+                came_from.insert(key, loc);
+                let estimate = neighbour_cost.saturating_add(rules.estimated_cost(&neighbour, &goal));
+                frontier.push(Reverse((estimate, Reverse(neighbour_cost), neighbour.y, neighbour.x)));
+            }
+        }
+    }
+    Err(NoPath::Unreachable { num_locs_explored: best_costs.len() })
+}
+
+fn search_window(loc_grid: &LocationGrid, start: &Loc, goal: &Loc) -> (Loc, Loc) {
+    let corners = [*start, *goal].into_iter()
+        .chain(loc_grid.bounds().into_iter().flat_map(|(min, max)| [min, max]))
+        .collect_vec();
+    let min = Loc::new(corners.iter().map(|loc| loc.x).min().unwrap() - 1,
+                       corners.iter().map(|loc| loc.y).min().unwrap() - 1);
+    let max = Loc::new(corners.iter().map(|loc| loc.x).max().unwrap() + 1,
+                       corners.iter().map(|loc| loc.y).max().unwrap() + 1);
+    (min, max)
+}
+
+fn trace_path(came_from: &HashMap<(i64, i64), Loc>, goal: Loc) -> Vec<Loc> {
+    let mut locs = vec![goal];
+    while let Some(&previous) = came_from.get(&(locs.last().unwrap().x, locs.last().unwrap().y)) {
+        locs.push(previous);
+    }
+    locs.reverse();
+    locs
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::day5path::*;
+    use crate::util::to_string_iter;
+
+    #[test]
+    fn straight_path_without_vents() {
+        let path = day5_safe_path(to_string_iter(vec![]), Loc::new(0, 0), Loc::new(2, 1), &PathRules::new(Moves::FourConnected)).unwrap();
+        assert_eq!(path.cost, 3);
+        assert_eq!(path.locs.len(), 4);
+        assert_eq!(path.locs[0], Loc::new(0, 0));
+        assert_eq!(path.locs[3], Loc::new(2, 1));
+    }
+
+    #[test]
+    fn diagonal_moves_when_eight_connected() {
+        let path = day5_safe_path(to_string_iter(vec![]), Loc::new(0, 0), Loc::new(3, 3), &PathRules::new(Moves::EightConnected)).unwrap();
+        assert_eq!(path, SafePath {
+            locs: vec![Loc::new(0, 0), Loc::new(1, 1), Loc::new(2, 2), Loc::new(3, 3)],
+            cost: 3,
+        });
+    }
+
+    #[test]
+    fn long_path_across_open_ground() {
+        let loc_grid = LocationGrid::new();
+        let rules = PathRules::new(Moves::FourConnected);
+        let path = find_safe_path(&loc_grid, Loc::new(0, 0), Loc::new(10_000, 10_000), &rules).unwrap();
+        assert_eq!(path.cost, 20_000);
+        assert_eq!(path.locs.len(), 20_001);
+    }
+
+    #[test]
+    fn huge_costs_do_not_overflow() {
+        let input = vec!["2,-1 -> 2,1"];
+        let mut rules = PathRules::new(Moves::FourConnected);
+        rules.impassable_at = None;
+        rules.vent_cost = u64::MAX;
+        let path = day5_safe_path(to_string_iter(input.clone()), Loc::new(0, 0), Loc::new(4, 0), &rules).unwrap();
+        assert_eq!(path.cost, 8);
+        rules.step_cost = u64::MAX / 2;
+        let path = day5_safe_path(to_string_iter(input), Loc::new(0, 0), Loc::new(1, 0), &rules).unwrap();
+        assert_eq!(path.cost, u64::MAX / 2);
+    }
+
+    #[test]
+    fn path_goes_around_dangerous_wall() {
+        let input = to_string_iter(vec![
+            "2,-5 -> 2,5",
+            "2,5 -> 2,-5",
+        ]);
+        let path = day5_safe_path(input, Loc::new(0, 0), Loc::new(4, 0), &PathRules::new(Moves::FourConnected)).unwrap();
+        assert_eq!(path.cost, 16);
+        assert!(path.locs.iter().filter(|loc| loc.x == 2).all(|loc| loc.y.abs() == 6));
+    }
+
+    #[test]
+    fn path_weighs_vent_cost_against_detour() {
+        let input = vec!["2,-1 -> 2,1"];
+        let mut rules = PathRules::new(Moves::FourConnected);
+        let path = day5_safe_path(to_string_iter(input.clone()), Loc::new(0, 0), Loc::new(4, 0), &rules).unwrap();
+        assert_eq!(path.cost, 8);
+        rules.vent_cost = 2;
+        let path = day5_safe_path(to_string_iter(input), Loc::new(0, 0), Loc::new(4, 0), &rules).unwrap();
+        assert_eq!(path.cost, 6);
+        assert_eq!(path.locs, (0..=4).map(|x| Loc::new(x, 0)).collect_vec());
+    }
+
+    #[test]
+    fn dangerous_locs_are_passable_without_impassable_rule() {
+        let input = to_string_iter(vec![
+            "2,-5 -> 2,5",
+            "2,5 -> 2,-5",
+        ]);
+        let mut rules = PathRules::new(Moves::FourConnected);
+        rules.impassable_at = None;
+        rules.vent_cost = 1;
+        let path = day5_safe_path(input, Loc::new(0, 0), Loc::new(4, 0), &rules).unwrap();
+        assert_eq!(path.cost, 6);
+    }
+
+    #[test]
+    fn no_path_when_goal_is_walled_off() {
+        let input = to_string_iter(vec![
+            "0,0 -> 4,0",
+            "0,4 -> 4,4",
+            "0,0 -> 0,4",
+            "4,0 -> 4,4",
+        ]);
+        let mut rules = PathRules::new(Moves::EightConnected);
+        rules.impassable_at = Some(1);
+        let result = day5_safe_path(input, Loc::new(2, 2), Loc::new(9, 9), &rules);
+        assert_eq!(result, Err(NoPath::Unreachable { num_locs_explored: 9 }));
+        assert_eq!(result.unwrap_err().to_string(), "goal is walled off by impassable locs (9 locs reachable from start)");
+    }
+
+    #[test]
+    fn no_path_when_start_or_goal_is_dangerous() {
+        let input = vec![
+            "0,0 -> 4,0",
+            "2,0 -> 2,3",
+        ];
+        let rules = PathRules::new(Moves::FourConnected);
+        let result = day5_safe_path(to_string_iter(input.clone()), Loc::new(2, 0), Loc::new(9, 9), &rules);
+        assert_eq!(result, Err(NoPath::StartImpassable { num_vents: 2 }));
+        assert_eq!(result.unwrap_err().to_string(), "start has 2 vents, which makes it impassable");
+        let result = day5_safe_path(to_string_iter(input), Loc::new(9, 9), Loc::new(2, 0), &rules);
+        assert_eq!(result, Err(NoPath::GoalImpassable { num_vents: 2 }));
+    }
+}
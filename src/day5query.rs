@@ -0,0 +1,409 @@
+use std::collections::HashMap;
+use itertools::Itertools;
+use crate::day5::{DEFAULT_DANGER_THRESHOLD, gcd, LineSegment, Loc, read_vent_lines};
+
+const MIN_BUCKET_SIZE: i64 = 16;
+// Buckets only grow past the typical line's length when the longest line would otherwise cross
+// more than about this many per axis.
+const MAX_BUCKETS_PER_LINE: i64 = 1 << 12;
+
+type Bucket = (i64, i64);
+
+#[allow(dead_code)]
+pub fn day5_vent_index(input: impl Iterator<Item=String>) -> VentIndex {
+    let vent_lines = read_vent_lines(input);
+    VentIndex::new(vent_lines)
+}
+
+// Vent lines bucketed into square tiles of the plane, so that queries only look at the
+// lines near the region they ask about. Each line is in every bucket it passes through, and
+// covers the locs lying exactly on it.
+pub struct VentIndex {
+    vent_lines: Vec<LineSegment>,
+    bucket_size: i64,
+    line_buckets: HashMap<Bucket, Vec<usize>>,
+    danger_threshold: u16,
+}
+
+#[allow(dead_code)]
+impl VentIndex {
+    pub fn new(vent_lines: Vec<LineSegment>) -> Self {
+        let extents = vent_lines.iter()
+            .map(|vent_line| vent_line.0.x.abs_diff(vent_line.1.x).max(vent_line.0.y.abs_diff(vent_line.1.y)) as i64)
+            .sorted()
+            .collect_vec();
+        let median_extent = extents.get(extents.len() / 2).copied().unwrap_or(0);
+        let longest_extent = extents.last().copied().unwrap_or(0);
+        let bucket_size = MIN_BUCKET_SIZE.max(median_extent).max(longest_extent / MAX_BUCKETS_PER_LINE + 1);
+        let mut index = VentIndex {
+            vent_lines: vec![],
+            bucket_size,
+            line_buckets: HashMap::new(),
+            danger_threshold: DEFAULT_DANGER_THRESHOLD,
+        };
+        for (line_index, vent_line) in vent_lines.iter().enumerate() {
+            for bucket in index.crossed_buckets(vent_line) {
+                index.line_buckets.entry(bucket).or_default().push(line_index);
+            }
+        }
+        index.vent_lines = vent_lines;
+        index
+    }
+
+    pub fn with_danger_threshold(mut self, danger_threshold: u16) -> Self {
+        assert!(danger_threshold > 0, "Danger threshold must be at least 1");
+        self.danger_threshold = danger_threshold;
+        self
+    }
+
+    // Every vent loc inside the rectangle with corners min and max, with its vent count, in row order.
+    pub fn vents_in_rect(&self, min: &Loc, max: &Loc) -> Vec<(Loc, u16)> {
+        let (min_bucket, max_bucket) = self.bucket_range(min, max);
+        let mut vent_counts: HashMap<(i64, i64), u16> = HashMap::new();
+        for line_index in self.lines_in_buckets(min_bucket, max_bucket) {
+            for loc in clipped_locs(&self.vent_lines[line_index], min, max) {
+                *vent_counts.entry((loc.x, loc.y)).or_insert(0) += 1;
+            }
+        }
+        vent_counts.into_iter()
+            .map(|((x, y), count)| (Loc::new(x, y), count))
+            .sorted_by_key(|(loc, _count)| (loc.y, loc.x))
+            .collect()
+    }
+
+    // True if walking straight from one point to the other touches any vent line, even between locs.
+    pub fn path_crosses_vent(&self, from: &Loc, to: &Loc) -> bool {
+        let path = LineSegment(*from, *to);
+        let (min_bucket, max_bucket) = self.bucket_range(from, to);
+        self.lines_in_buckets(min_bucket, max_bucket).into_iter()
+            .any(|line_index| self.vent_lines[line_index].intersects(&path))
+    }
+
+    // The dangerous loc closest to the point by straight-line distance, ties going to the
+    // first in row order. Buckets are searched in rings outward from the point's bucket,
+    // starting at the first ring that reaches any lines. Once the rings would visit more
+    // buckets than have lines, the buckets with lines are searched nearest first instead.
+    pub fn nearest_dangerous_loc(&self, point: &Loc) -> Option<Loc> {
+        let (min_bucket, max_bucket) = bucket_extent(self.line_buckets.keys())?;
+        let center = self.bucket(point);
+        let first_ring = [
+            min_bucket.0 - center.0, center.0 - max_bucket.0,
+            min_bucket.1 - center.1, center.1 - max_bucket.1,
+        ].into_iter().max().unwrap().max(0);
+        let last_ring = [
+            center.0 - min_bucket.0, max_bucket.0 - center.0,
+            center.1 - min_bucket.1, max_bucket.1 - center.1,
+        ].into_iter().max().unwrap();
+
+        let mut nearest: Option<(i128, i64, i64)> = None;
+        let mut num_buckets_visited = 0;
+        for ring in first_ring..=last_ring {
+            num_buckets_visited += if ring == 0 { 1 } else { 8 * ring as i128 };
+            if num_buckets_visited > self.line_buckets.len() as i128 {
+                return self.nearest_dangerous_loc_by_bucket_distance(point);
+            }
+            let ring_nearest = ring_buckets(center, ring)
+                .filter(|bucket| self.line_buckets.contains_key(bucket))
+                .flat_map(|bucket| self.dangerous_locs_in_bucket(bucket))
+                .map(|loc| (squared_distance(point, &loc), loc.y, loc.x))
+                .min();
+            nearest = nearest.into_iter().chain(ring_nearest).min();
+            // Locs in the next ring out are more than ring * bucket_size away along some axis.
+            let ring_distance = ring as i128 * self.bucket_size as i128;
+            if nearest.is_some_and(|(distance, _y, _x)| distance <= ring_distance * ring_distance) {
+                break;
+            }
+        }
+        nearest.map(|(_distance, y, x)| Loc::new(x, y))
+    }
+
+    fn nearest_dangerous_loc_by_bucket_distance(&self, point: &Loc) -> Option<Loc> {
+        let buckets = self.line_buckets.keys()
+            .map(|&bucket| (self.squared_distance_to_bucket(point, bucket), bucket))
+            .sorted();
+        let mut nearest: Option<(i128, i64, i64)> = None;
+        for (bucket_distance, bucket) in buckets {
+            if nearest.is_some_and(|(distance, _y, _x)| distance < bucket_distance) {
+                break;
+            }
+            let bucket_nearest = self.dangerous_locs_in_bucket(bucket).into_iter()
+                .map(|loc| (squared_distance(point, &loc), loc.y, loc.x))
+                .min();
+            nearest = nearest.into_iter().chain(bucket_nearest).min();
+        }
+        nearest.map(|(_distance, y, x)| Loc::new(x, y))
+    }
+
+    fn squared_distance_to_bucket(&self, point: &Loc, bucket: Bucket) -> i128 {
+        let min = Loc::new(bucket.0 * self.bucket_size, bucket.1 * self.bucket_size);
+        let max = Loc::new(min.x + self.bucket_size - 1, min.y + self.bucket_size - 1);
+        squared_distance(point, &Loc::new(point.x.clamp(min.x, max.x), point.y.clamp(min.y, max.y)))
+    }
+
+    fn dangerous_locs_in_bucket(&self, bucket: Bucket) -> Vec<Loc> {
+        let min = Loc::new(bucket.0 * self.bucket_size, bucket.1 * self.bucket_size);
+        let max = Loc::new(min.x + self.bucket_size - 1, min.y + self.bucket_size - 1);
+        self.vents_in_rect(&min, &max).into_iter()
+            .filter(|&(_loc, count)| count >= self.danger_threshold)
+            .map(|(loc, _count)| loc)
+            .collect()
+    }
+
+    // Every bucket the line passes through, treating it as continuous. Each column of buckets
+    // takes the part of the line up to the column's far edge, so a line running along an edge
+    // may also be put in the bucket beside it.
+    fn crossed_buckets(&self, vent_line: &LineSegment) -> Vec<Bucket> {
+        let (start, end) = if (vent_line.0.x, vent_line.0.y) <= (vent_line.1.x, vent_line.1.y) {
+            (vent_line.0, vent_line.1)
+        } else {
+            (vent_line.1, vent_line.0)
+        };
+        let (dx, dy) = ((end.x - start.x) as i128, (end.y - start.y) as i128);
+        let size = self.bucket_size;
+        // The bucket row of the line where it reaches x, rounding down.
+        let row_at = |x: i64| {
+            let numerator = start.y as i128 * dx + (x - start.x) as i128 * dy;
+            numerator.div_euclid(dx * size as i128) as i64
+        };
+        let mut buckets = vec![];
+        for column in start.x.div_euclid(size)..=end.x.div_euclid(size) {
+            let (row1, row2) = if dx == 0 {
+                (start.y.div_euclid(size), end.y.div_euclid(size))
+            } else {
+                (row_at(start.x.max(column * size)), row_at(end.x.min(column * size + size)))
+            };
+            buckets.extend((row1.min(row2)..=row1.max(row2)).map(|row| (column, row)));
+        }
+        buckets
+    }
+
+    fn bucket(&self, loc: &Loc) -> Bucket {
+        (loc.x.div_euclid(self.bucket_size), loc.y.div_euclid(self.bucket_size))
+    }
+
+    fn bucket_range(&self, corner1: &Loc, corner2: &Loc) -> (Bucket, Bucket) {
+        let min = Loc::new(corner1.x.min(corner2.x), corner1.y.min(corner2.y));
+        let max = Loc::new(corner1.x.max(corner2.x), corner1.y.max(corner2.y));
+        (self.bucket(&min), self.bucket(&max))
+    }
+
+    fn lines_in_buckets(&self, min_bucket: Bucket, max_bucket: Bucket) -> Vec<usize> {
+        let num_buckets_in_range = (max_bucket.0 - min_bucket.0 + 1) as i128 * (max_bucket.1 - min_bucket.1 + 1) as i128;
+        let line_indexes: Vec<usize> = if num_buckets_in_range > self.line_buckets.len() as i128 {
+            self.line_buckets.iter()
+                .filter(|(bucket, _line_indexes)| is_bucket_between(**bucket, min_bucket, max_bucket))
+                .flat_map(|(_bucket, line_indexes)| line_indexes.iter().copied())
+                .collect()
+        } else {
+            buckets_between(min_bucket, max_bucket)
+                .filter_map(|bucket| self.line_buckets.get(&bucket))
+                .flat_map(|line_indexes| line_indexes.iter().copied())
+                .collect()
+        };
+        line_indexes.into_iter().sorted().dedup().collect()
+    }
+}
+
+fn buckets_between(min_bucket: Bucket, max_bucket: Bucket) -> impl Iterator<Item=Bucket> {
+    (min_bucket.1..=max_bucket.1).cartesian_product(min_bucket.0..=max_bucket.0)
+        .map(|(y, x)| (x, y))
+}
+
+fn is_bucket_between(bucket: Bucket, min_bucket: Bucket, max_bucket: Bucket) -> bool {
+    (min_bucket.0..=max_bucket.0).contains(&bucket.0) && (min_bucket.1..=max_bucket.1).contains(&bucket.1)
+}
+
+fn bucket_extent<'a>(buckets: impl Iterator<Item=&'a Bucket> + Clone) -> Option<(Bucket, Bucket)> {
+    let (min_x, max_x) = buckets.clone().map(|bucket| bucket.0).minmax().into_option()?;
+    let (min_y, max_y) = buckets.map(|bucket| bucket.1).minmax().into_option()?;
+    Some(((min_x, min_y), (max_x, max_y)))
+}
+
+// The buckets exactly ring steps away from the center, counting diagonal steps as one.
+fn ring_buckets(center: Bucket, ring: i64) -> Box<dyn Iterator<Item=Bucket>> {
+    if ring == 0 {
+        return Box::new(std::iter::once(center));
+    }
+    let (x, y) = center;
+    let top_and_bottom = (-ring..=ring)
+        .flat_map(move |offset| [(x + offset, y - ring), (x + offset, y + ring)]);
+    let sides = (-ring + 1..ring)
+        .flat_map(move |offset| [(x - ring, y + offset), (x + ring, y + offset)]);
+    Box::new(top_and_bottom.chain(sides))
+}
+
+fn squared_distance(loc1: &Loc, loc2: &Loc) -> i128 {
+    let dx = (loc1.x - loc2.x) as i128;
+    let dy = (loc1.y - loc2.y) as i128;
+    dx * dx + dy * dy
+}
+
+// The locs lying exactly on a vent line that are inside the rectangle, found without walking
+// the parts of the line outside it.
+fn clipped_locs(vent_line: &LineSegment, min: &Loc, max: &Loc) -> Vec<Loc> {
+    let (dx, dy) = (vent_line.1.x - vent_line.0.x, vent_line.1.y - vent_line.0.y);
+    let num_steps = gcd(dx.abs(), dy.abs());
+    let (step_x, step_y) = if num_steps == 0 { (0, 0) } else { (dx / num_steps, dy / num_steps) };
+    let x_steps = steps_within(vent_line.0.x, step_x, min.x, max.x);
+    let y_steps = steps_within(vent_line.0.y, step_y, min.y, max.y);
+    let (first_step, last_step) = match (x_steps, y_steps) {
+        (Some(x_steps), Some(y_steps)) => (x_steps.0.max(y_steps.0).max(0), x_steps.1.min(y_steps.1).min(num_steps)),
+        _ => return vec![],
+    };
+    (first_step..=last_step)
+        .map(|step| Loc::new(vent_line.0.x + step * step_x, vent_line.0.y + step * step_y))
+        .collect()
+}
+
+// The range of steps from start that keep a coordinate between low and high.
+fn steps_within(start: i64, step: i64, low: i64, high: i64) -> Option<(i64, i64)> {
+    match step.signum() {
+        0 if (low..=high).contains(&start) => Some((i64::MIN, i64::MAX)),
+        0 => None,
+        1 => Some((ceil_div(low - start, step), (high - start).div_euclid(step))),
+        _ => Some((ceil_div(start - high, -step), (start - low).div_euclid(-step))),
+    }
+}
+
+fn ceil_div(numerator: i64, denominator: i64) -> i64 {
+    -(-numerator).div_euclid(denominator)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::day5query::*;
+    use crate::util::to_string_iter;
+
+    fn example_index() -> VentIndex {
+        day5_vent_index(to_string_iter(vec![
+            "0,9 -> 5,9",
+            "8,0 -> 0,8",
+            "9,4 -> 3,4",
+            "2,2 -> 2,1",
+            "7,0 -> 7,4",
+            "6,4 -> 2,0",
+            "0,9 -> 2,9",
+            "3,4 -> 1,4",
+            "0,0 -> 8,8",
+            "5,5 -> 8,2",
+        ]))
+    }
+
+    #[test]
+    fn vents_in_rect_of_example() {
+        assert_eq!(example_index().vents_in_rect(&Loc::new(0, 0), &Loc::new(2, 1)),
+                   vec![(Loc::new(0, 0), 1), (Loc::new(2, 0), 1), (Loc::new(1, 1), 1), (Loc::new(2, 1), 1)]);
+        assert_eq!(example_index().vents_in_rect(&Loc::new(3, 4), &Loc::new(4, 5)),
+                   vec![(Loc::new(3, 4), 2), (Loc::new(4, 4), 3), (Loc::new(3, 5), 1)]);
+    }
+
+    #[test]
+    fn vents_in_rect_outside_every_line() {
+        assert_eq!(example_index().vents_in_rect(&Loc::new(-50, -50), &Loc::new(-1, 100)), vec![]);
+    }
+
+    #[test]
+    fn vents_in_rect_clips_long_lines() {
+        let index = VentIndex::new(vec![
+            LineSegment(Loc::new(-4_000_000_000, 3), Loc::new(4_000_000_000, 3)),
+            LineSegment(Loc::new(3_000_000_000, 3_000_000_000), Loc::new(-3_000_000_000, -3_000_000_000)),
+        ]);
+        assert_eq!(index.vents_in_rect(&Loc::new(2, 2), &Loc::new(4, 4)),
+                   vec![(Loc::new(2, 2), 1), (Loc::new(2, 3), 1), (Loc::new(3, 3), 2), (Loc::new(4, 3), 1), (Loc::new(4, 4), 1)]);
+    }
+
+    #[test]
+    fn vents_in_rect_of_lines_at_any_slope() {
+        let index = VentIndex::new(vec![
+            LineSegment(Loc::new(0, 0), Loc::new(2, 6)),
+            LineSegment(Loc::new(7, 1), Loc::new(-3, 6)),
+        ]);
+        assert_eq!(index.vents_in_rect(&Loc::new(0, 1), &Loc::new(9, 5)),
+                   vec![(Loc::new(7, 1), 1), (Loc::new(5, 2), 1), (Loc::new(1, 3), 1), (Loc::new(3, 3), 1),
+                        (Loc::new(1, 4), 1)]);
+    }
+
+    #[test]
+    fn one_long_line_does_not_widen_the_buckets() {
+        let mut vent_lines = (0..100)
+            .map(|y| LineSegment(Loc::new(0, y), Loc::new(10, y)))
+            .collect_vec();
+        vent_lines.push(LineSegment(Loc::new(-30_000, 5), Loc::new(30_000, 5)));
+        let index = VentIndex::new(vent_lines);
+        assert_eq!(index.bucket_size, MIN_BUCKET_SIZE);
+        assert_eq!(index.lines_in_buckets((0, 0), (0, 0)), (0..16).chain([100]).collect_vec());
+        assert_eq!(index.vents_in_rect(&Loc::new(4, 5), &Loc::new(5, 5)), vec![(Loc::new(4, 5), 2), (Loc::new(5, 5), 2)]);
+    }
+
+    #[test]
+    fn lines_are_only_in_the_buckets_they_cross() {
+        let index = VentIndex::new(vec![
+            LineSegment(Loc::new(0, 0), Loc::new(63, 63)),
+            LineSegment(Loc::new(100, 100), Loc::new(101, 100)),
+            LineSegment(Loc::new(200, 200), Loc::new(201, 200)),
+        ]);
+        assert_eq!(index.line_buckets.keys().copied().sorted().collect_vec(),
+                   vec![(0, 0), (0, 1), (1, 1), (1, 2), (2, 2), (2, 3), (3, 3), (6, 6), (12, 12)]);
+        assert!(index.path_crosses_vent(&Loc::new(20, 16), &Loc::new(15, 21)));
+    }
+
+    #[test]
+    fn path_crosses_vent() {
+        let index = example_index();
+        assert!(!index.path_crosses_vent(&Loc::new(0, 5), &Loc::new(0, 7)));
+        assert!(index.path_crosses_vent(&Loc::new(0, 5), &Loc::new(0, 8)));
+        assert!(index.path_crosses_vent(&Loc::new(3, 2), &Loc::new(4, 1)));
+        assert!(!index.path_crosses_vent(&Loc::new(20, -5), &Loc::new(-5, -1)));
+    }
+
+    #[test]
+    fn nearest_dangerous_loc() {
+        let index = example_index();
+        assert_eq!(index.nearest_dangerous_loc(&Loc::new(0, 0)), Some(Loc::new(2, 2)));
+        assert_eq!(index.nearest_dangerous_loc(&Loc::new(9, 0)), Some(Loc::new(7, 1)));
+        assert_eq!(index.nearest_dangerous_loc(&Loc::new(4, 4)), Some(Loc::new(4, 4)));
+        assert_eq!(index.nearest_dangerous_loc(&Loc::new(100, 100)), Some(Loc::new(7, 4)));
+        assert_eq!(index.nearest_dangerous_loc(&Loc::new(-100, -100)), Some(Loc::new(2, 2)));
+    }
+
+    #[test]
+    fn nearest_dangerous_loc_breaks_ties_in_row_order() {
+        let index = VentIndex::new(vec![
+            LineSegment(Loc::new(0, 0), Loc::new(4, 0)),
+            LineSegment(Loc::new(0, 0), Loc::new(0, 4)),
+            LineSegment(Loc::new(4, 0), Loc::new(4, 4)),
+        ]);
+        assert_eq!(index.nearest_dangerous_loc(&Loc::new(2, 1)), Some(Loc::new(0, 0)));
+    }
+
+    #[test]
+    fn nearest_dangerous_loc_far_from_every_line() {
+        let index = example_index();
+        assert_eq!(index.nearest_dangerous_loc(&Loc::new(1_000_000_000_000, -5)), Some(Loc::new(7, 1)));
+        assert_eq!(index.nearest_dangerous_loc(&Loc::new(-3_000_000_000, 4_000_000_000)), Some(Loc::new(0, 9)));
+    }
+
+    #[test]
+    fn nearest_dangerous_loc_between_far_apart_lines() {
+        let index = VentIndex::new(vec![
+            LineSegment(Loc::new(0, 0), Loc::new(2, 0)),
+            LineSegment(Loc::new(2, 0), Loc::new(2, 2)),
+            LineSegment(Loc::new(1_000_000, 0), Loc::new(1_000_002, 0)),
+            LineSegment(Loc::new(1_000_000, 0), Loc::new(1_000_000, 2)),
+        ]);
+        assert_eq!(index.nearest_dangerous_loc(&Loc::new(600_000, 3)), Some(Loc::new(1_000_000, 0)));
+        assert_eq!(index.nearest_dangerous_loc(&Loc::new(400_000, 3)), Some(Loc::new(2, 0)));
+    }
+
+    #[test]
+    fn nearest_dangerous_loc_without_any() {
+        let index = VentIndex::new(vec![LineSegment(Loc::new(0, 0), Loc::new(4, 0))]);
+        assert_eq!(index.nearest_dangerous_loc(&Loc::new(2, 1)), None);
+    }
+
+    #[test]
+    fn nearest_loc_at_danger_threshold() {
+        let index = example_index().with_danger_threshold(3);
+        assert_eq!(index.nearest_dangerous_loc(&Loc::new(0, 0)), Some(Loc::new(4, 4)));
+    }
+}
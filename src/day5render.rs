@@ -0,0 +1,384 @@
+use itertools::Itertools;
+use crate::day5::{LocationGrid, Loc, read_vent_lines};
+
+#[allow(dead_code)]
+pub fn day5_vent_map(input: impl Iterator<Item=String>, window: Option<(Loc, Loc)>) -> String {
+    let loc_grid = read_location_grid(input);
+    match window.or_else(|| loc_grid.bounds()) {
+        Some((min, max)) => render_text(&loc_grid, &min, &max),
+        None => String::new(),
+    }
+}
+
+#[allow(dead_code)]
+pub fn day5_heatmap_png(input: impl Iterator<Item=String>, window: Option<(Loc, Loc)>, cell_size: u32) -> Vec<u8> {
+    let loc_grid = read_location_grid(input);
+    let (min, max) = window.or_else(|| loc_grid.bounds())
+        .unwrap_or((Loc::new(0, 0), Loc::new(0, 0)));
+    render_png(&loc_grid, &min, &max, cell_size)
+}
+
+fn read_location_grid(input: impl Iterator<Item=String>) -> LocationGrid {
+    LocationGrid::from_vent_lines(read_vent_lines(input))
+}
+
+// One character per loc in the window, rows top to bottom: '.' for no vents, the count up
+// to 9, and '+' for more.
+pub fn render_text(loc_grid: &LocationGrid, min: &Loc, max: &Loc) -> String {
+    if min.x <= max.x && min.y <= max.y {
+        let num_locs = span(min.x, max.x).zip(span(min.y, max.y))
+            .and_then(|(width, height)| width.checked_mul(height));
+        assert!(num_locs.is_some_and(|num_locs| num_locs as u64 <= MAX_RENDER_SIZE),
+                "Window {:?} to {:?} has more than {} locs to render", min, max, MAX_RENDER_SIZE);
+    }
+    (min.y..=max.y)
+        .map(|y| (min.x..=max.x)
+            .map(|x| count_to_char(loc_grid.vent_count(&Loc::new(x, y))))
+            .collect::<String>())
+        .map(|row| row + "\n")
+        .collect()
+}
+
+// How many locs from low to high inclusive, if that fits in an i64.
+fn span(low: i64, high: i64) -> Option<i64> {
+    high.checked_sub(low).and_then(|span| span.checked_add(1))
+}
+
+fn count_to_char(count: u16) -> char {
+    match count {
+        0 => '.',
+        1..=9 => char::from_digit(count as u32, 10).unwrap(),
+        _ => '+',
+    }
+}
+
+// An RGB PNG with each loc drawn as a cell_size square, black for no vents and running
+// through red and yellow to white at the highest count in the window.
+pub fn render_png(loc_grid: &LocationGrid, min: &Loc, max: &Loc, cell_size: u32) -> Vec<u8> {
+    assert!(cell_size > 0, "Cell size must be at least 1");
+    assert!(min.x <= max.x && min.y <= max.y, "Window {:?} to {:?} is empty", min, max);
+    let image_size = |num_locs: Option<i64>| num_locs.and_then(|num_locs| u32::try_from(num_locs).ok())
+        .and_then(|num_locs| num_locs.checked_mul(cell_size))
+        .filter(|&size| size <= MAX_PNG_SIZE)
+        .unwrap_or_else(|| panic!("Window {:?} to {:?} is too big for a PNG at cell size {}", min, max, cell_size));
+    let width = image_size(span(min.x, max.x));
+    let height = image_size(span(min.y, max.y));
+    assert!(width as u64 * height as u64 <= MAX_RENDER_SIZE,
+            "Window {:?} to {:?} at cell size {} has more than {} pixels to render", min, max, cell_size, MAX_RENDER_SIZE);
+    let counts = (min.y..=max.y)
+        .map(|y| (min.x..=max.x)
+            .map(|x| loc_grid.vent_count(&Loc::new(x, y)))
+            .collect_vec())
+        .collect_vec();
+    let max_count = counts.iter().flatten().copied().max().unwrap_or(0);
+
+    let mut scanlines = vec![];
+    for row in &counts {
+        let mut scanline = vec![0];
+        for &count in row {
+            let colour = heat_colour(count, max_count);
+            (0..cell_size).for_each(|_| scanline.extend_from_slice(&colour));
+        }
+        (0..cell_size).for_each(|_| scanlines.extend_from_slice(&scanline));
+    }
+    encode_png(width, height, &scanlines)
+}
+
+fn heat_colour(count: u16, max_count: u16) -> [u8; 3] {
+    if count == 0 {
+        return [0, 0, 0];
+    }
+    let heat = 3.0 * count as f64 / max_count as f64;
+    let channel = |offset: f64| ((heat - offset).clamp(0.0, 1.0) * 255.0).round() as u8;
+    [channel(0.0), channel(1.0), channel(2.0)]
+}
+
+const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+const MAX_STORED_BLOCK_LEN: usize = 0xFFFF;
+const MAX_PNG_SIZE: u32 = (1 << 31) - 1;
+const MAX_IDAT_LEN: usize = 1 << 16;
+// The most pixels, or characters of text, to render: about 200MB of RGB.
+const MAX_RENDER_SIZE: u64 = 1 << 26;
+
+// Scanlines are already filtered (a leading filter type byte per row), and are stored
+// uncompressed in the zlib stream.
+fn encode_png(width: u32, height: u32, scanlines: &[u8]) -> Vec<u8> {
+    let mut header = vec![];
+    header.extend_from_slice(&width.to_be_bytes());
+    header.extend_from_slice(&height.to_be_bytes());
+    // 8-bit RGB, deflate, adaptive filtering, no interlace.
+    header.extend_from_slice(&[8, 2, 0, 0, 0]);
+
+    let mut png = PNG_SIGNATURE.to_vec();
+    write_chunk(&mut png, b"IHDR", &header);
+    for data in zlib_stored(scanlines).chunks(MAX_IDAT_LEN) {
+        write_chunk(&mut png, b"IDAT", data);
+    }
+    write_chunk(&mut png, b"IEND", &[]);
+    png
+}
+
+fn write_chunk(png: &mut Vec<u8>, chunk_type: &[u8; 4], data: &[u8]) {
+    let len = u32::try_from(data.len()).expect("PNG chunk data is too long");
+    png.extend_from_slice(&len.to_be_bytes());
+    let crc_start = png.len();
+    png.extend_from_slice(chunk_type);
+    png.extend_from_slice(data);
+    let crc = crc32(&png[crc_start..]);
+    png.extend_from_slice(&crc.to_be_bytes());
+}
+
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    let mut stream = vec![0x78, 0x01];
+    let blocks = data.chunks(MAX_STORED_BLOCK_LEN).collect_vec();
+    if blocks.is_empty() {
+        stream.extend_from_slice(&[1, 0, 0, 0xFF, 0xFF]);
+    }
+    for (block_index, block) in blocks.iter().enumerate() {
+        let is_final = block_index + 1 == blocks.len();
+        let len = block.len() as u16;
+        stream.push(is_final as u8);
+        stream.extend_from_slice(&len.to_le_bytes());
+        stream.extend_from_slice(&(!len).to_le_bytes());
+        stream.extend_from_slice(block);
+    }
+    stream.extend_from_slice(&adler32(data).to_be_bytes());
+    stream
+}
+
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+    for &byte in bytes {
+        crc ^= byte as u32;
+        for _bit in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+fn adler32(bytes: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in bytes {
+        a = (a + byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::day5render::*;
+    use crate::util::to_string_iter;
+
+    fn example_input() -> impl Iterator<Item=String> {
+        to_string_iter(vec![
+            "0,9 -> 5,9",
+            "8,0 -> 0,8",
+            "9,4 -> 3,4",
+            "2,2 -> 2,1",
+            "7,0 -> 7,4",
+            "6,4 -> 2,0",
+            "0,9 -> 2,9",
+            "3,4 -> 1,4",
+            "0,0 -> 8,8",
+            "5,5 -> 8,2",
+        ])
+    }
+
+    #[test]
+    fn example_vent_map() {
+        assert_eq!(day5_vent_map(example_input(), None),
+                   "1.1....11.\n\
+                    .111...2..\n\
+                    ..2.1.111.\n\
+                    ...1.2.2..\n\
+                    .112313211\n\
+                    ...1.2....\n\
+                    ..1...1...\n\
+                    .1.....1..\n\
+                    1.......1.\n\
+                    222111....\n");
+    }
+
+    #[test]
+    fn vent_map_for_window() {
+        let window = (Loc::new(-1, 3), Loc::new(3, 5));
+        assert_eq!(day5_vent_map(example_input(), Some(window)),
+                   "....1\n\
+                    ..112\n\
+                    ....1\n");
+    }
+
+    #[test]
+    fn vent_map_shows_high_counts_as_plus() {
+        let input = to_string_iter(vec!["0,0 -> 1,0"; 12]);
+        assert_eq!(day5_vent_map(input, None), "++\n");
+    }
+
+    #[test]
+    fn crc32_of_known_inputs() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+        assert_eq!(crc32(b"IEND"), 0xAE42_6082);
+    }
+
+    #[test]
+    fn adler32_of_known_input() {
+        assert_eq!(adler32(b"Wikipedia"), 0x11E6_0398);
+    }
+
+    #[test]
+    fn zlib_stored_splits_long_data_into_blocks() {
+        let data = vec![7; MAX_STORED_BLOCK_LEN + 1];
+        let stream = zlib_stored(&data);
+        assert_eq!(stream.len(), 2 + 5 + MAX_STORED_BLOCK_LEN + 5 + 1 + 4);
+        assert_eq!(&stream[..7], &[0x78, 0x01, 0, 0xFF, 0xFF, 0, 0]);
+        assert_eq!(&stream[7 + MAX_STORED_BLOCK_LEN..][..5], &[1, 1, 0, 0xFE, 0xFF]);
+    }
+
+    #[test]
+    fn heatmap_png_layout() {
+        let png = day5_heatmap_png(example_input(), None, 3);
+        assert_eq!(&png[..8], &PNG_SIGNATURE);
+        assert_eq!(&png[12..16], b"IHDR");
+        assert_eq!(&png[16..24], &[0, 0, 0, 30, 0, 0, 0, 30]);
+        let scanlines_len = 30 * (1 + 30 * 3);
+        let idat_len = 2 + 5 + scanlines_len + 4;
+        assert_eq!(&png[33..37], &(idat_len as u32).to_be_bytes());
+        assert_eq!(&png[37..41], b"IDAT");
+        assert_eq!(&png[png.len() - 12..], &[0, 0, 0, 0, b'I', b'E', b'N', b'D', 0xAE, 0x42, 0x60, 0x82]);
+    }
+
+    // Checks every chunk's CRC and the zlib checksum, and returns the width, height and
+    // unfiltered RGB bytes.
+    fn decode_png(png: &[u8]) -> (u32, u32, Vec<u8>) {
+        assert_eq!(&png[..8], &PNG_SIGNATURE);
+        let mut chunks = vec![];
+        let mut rest = &png[8..];
+        while !rest.is_empty() {
+            let len = u32::from_be_bytes(rest[..4].try_into().unwrap()) as usize;
+            let (typed_data, crc) = (&rest[4..8 + len], &rest[8 + len..12 + len]);
+            assert_eq!(crc32(typed_data).to_be_bytes(), crc);
+            chunks.push((&typed_data[..4], &typed_data[4..]));
+            rest = &rest[12 + len..];
+        }
+        let (first, last) = (chunks.first().unwrap(), chunks.last().unwrap());
+        assert_eq!((first.0, last.0), (&b"IHDR"[..], &b"IEND"[..]));
+        let header = first.1;
+        let width = u32::from_be_bytes(header[..4].try_into().unwrap());
+        let height = u32::from_be_bytes(header[4..8].try_into().unwrap());
+
+        let idats = &chunks[1..chunks.len() - 1];
+        assert!(idats.iter().all(|(chunk_type, data)| *chunk_type == b"IDAT" && data.len() <= MAX_IDAT_LEN));
+        let stream = idats.iter().flat_map(|(_chunk_type, data)| data.iter().copied()).collect_vec();
+        assert_eq!(&stream[..2], &[0x78, 0x01]);
+        let mut scanlines = vec![];
+        let mut block = &stream[2..];
+        loop {
+            let len = u16::from_le_bytes([block[1], block[2]]);
+            assert_eq!(u16::from_le_bytes([block[3], block[4]]), !len);
+            scanlines.extend_from_slice(&block[5..5 + len as usize]);
+            let is_final = block[0] == 1;
+            block = &block[5 + len as usize..];
+            if is_final {
+                break;
+            }
+        }
+        assert_eq!(block, &adler32(&scanlines).to_be_bytes());
+
+        let scanline_len = 1 + 3 * width as usize;
+        assert_eq!(scanlines.len(), scanline_len * height as usize);
+        let pixels = scanlines.chunks(scanline_len)
+            .flat_map(|scanline| {
+                assert_eq!(scanline[0], 0);
+                scanline[1..].to_vec()
+            })
+            .collect_vec();
+        (width, height, pixels)
+    }
+
+    #[test]
+    fn single_loc_png_matches_known_bytes() {
+        let png = day5_heatmap_png(to_string_iter(vec!["4,7 -> 4,7"]), None, 1);
+        assert_eq!(png, vec![
+            0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A,
+            0, 0, 0, 13, b'I', b'H', b'D', b'R', 0, 0, 0, 1, 0, 0, 0, 1, 8, 2, 0, 0, 0, 0x90, 0x77, 0x53, 0xDE,
+            0, 0, 0, 15, b'I', b'D', b'A', b'T', 0x78, 0x01, 1, 4, 0, 0xFB, 0xFF, 0, 255, 255, 255,
+            0x05, 0xFE, 0x02, 0xFE, 0x49, 0x66, 0x6E, 0x2B,
+            0, 0, 0, 0, b'I', b'E', b'N', b'D', 0xAE, 0x42, 0x60, 0x82,
+        ]);
+    }
+
+    #[test]
+    fn heatmap_png_decodes_to_heat_colours() {
+        let png = day5_heatmap_png(example_input(), None, 2);
+        let (width, height, pixels) = decode_png(&png);
+        assert_eq!((width, height), (20, 20));
+        let pixel = |x: usize, y: usize| &pixels[3 * (y * width as usize + x)..][..3];
+        // Locs (0, 0), (1, 0), (7, 1) and (4, 4), with 1, 0, 2 and 3 vents out of at most 3.
+        assert_eq!(pixel(0, 0), &[255, 0, 0]);
+        assert_eq!(pixel(1, 1), &[255, 0, 0]);
+        assert_eq!(pixel(2, 0), &[0, 0, 0]);
+        assert_eq!(pixel(15, 3), &[255, 255, 0]);
+        assert_eq!(pixel(9, 9), &[255, 255, 255]);
+    }
+
+    #[test]
+    fn large_png_splits_idat_into_chunks() {
+        let png = day5_heatmap_png(example_input(), None, 16);
+        let (width, height, pixels) = decode_png(&png);
+        assert_eq!((width, height), (160, 160));
+        assert_eq!(&pixels[3 * (159 * 160 + 159)..], &[0, 0, 0]);
+        assert_eq!(png.windows(4).filter(|window| window == b"IDAT").count(), 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "Cell size must be at least 1")]
+    fn png_needs_a_cell_size() {
+        day5_heatmap_png(example_input(), None, 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "is empty")]
+    fn png_needs_a_window_with_locs() {
+        day5_heatmap_png(example_input(), Some((Loc::new(3, 0), Loc::new(2, 5))), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "is too big for a PNG")]
+    fn png_size_must_fit() {
+        render_png(&LocationGrid::new(), &Loc::new(0, 0), &Loc::new(0, 0), 1 << 31);
+    }
+
+    #[test]
+    #[should_panic(expected = "pixels to render")]
+    fn png_pixel_count_must_fit() {
+        render_png(&LocationGrid::new(), &Loc::new(0, 0), &Loc::new(39999, 39999), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "locs to render")]
+    fn text_loc_count_must_fit() {
+        render_text(&LocationGrid::new(), &Loc::new(0, 0), &Loc::new(39999, 39999));
+    }
+
+    #[test]
+    #[should_panic(expected = "locs to render")]
+    fn text_window_size_does_not_overflow() {
+        render_text(&LocationGrid::new(), &Loc::new(i64::MIN, 0), &Loc::new(i64::MAX, 0));
+    }
+
+    #[test]
+    #[should_panic(expected = "is too big for a PNG")]
+    fn png_window_size_does_not_overflow() {
+        render_png(&LocationGrid::new(), &Loc::new(i64::MIN, 0), &Loc::new(i64::MAX, 0), 1);
+    }
+
+    #[test]
+    fn heat_colours_run_from_black_to_white() {
+        assert_eq!(heat_colour(0, 3), [0, 0, 0]);
+        assert_eq!(heat_colour(1, 3), [255, 0, 0]);
+        assert_eq!(heat_colour(2, 3), [255, 255, 0]);
+        assert_eq!(heat_colour(3, 3), [255, 255, 255]);
+    }
+}
@@ -1,6 +1,6 @@
 pub fn day6a(_input: impl Iterator<Item=String>) -> (&'static str, i32) {
     let answer = 5934;
-    ("day6a", answer as i32)
+    ("day6a", answer)
 }
 
 #[cfg(test)]
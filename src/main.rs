@@ -5,8 +5,23 @@ mod day1b;
 mod day2b;
 mod day3a;
 mod day3b;
+mod day3format;
+mod day3stats;
+mod day3stream;
 mod day4;
+mod day4caller;
+mod day4gen;
+mod day4lint;
+mod day4log;
+mod day4sim;
+mod day4stats;
 mod day5;
+mod day5clean;
+mod day5overlap;
+mod day5parallel;
+mod day5path;
+mod day5query;
+mod day5render;
 mod day6;
 
 fn main() {
@@ -1,6 +1,9 @@
 use std::fs::File;
-use std::{env, io};
+use std::{env, fmt, io};
+use std::any::type_name;
 use std::io::BufRead;
+use std::ops::{Index, IndexMut};
+use std::str::FromStr;
 
 pub fn read_input_file() -> impl Iterator<Item=String> {
     let filename = env::args().nth(1).expect("no input file given");
@@ -11,5 +14,672 @@ pub fn read_input_file() -> impl Iterator<Item=String> {
 
 #[cfg(test)]
 pub fn to_string_iter(strs: Vec<&'static str>) -> impl Iterator<Item=String> {
-    strs.into_iter().map(|str| String::from(str)).into_iter()
+    strs.into_iter().map(String::from)
+}
+
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Rng { state: seed }
+    }
+
+    // SplitMix64
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+
+    pub fn below(&mut self, bound: u64) -> u64 {
+        assert!(bound > 0);
+        let zone = u64::MAX - u64::MAX % bound;
+        loop {
+            let value = self.next_u64();
+            if value < zone {
+                return value % bound;
+            }
+        }
+    }
+
+    pub fn shuffle<T>(&mut self, items: &mut [T]) {
+        for i in (1..items.len()).rev() {
+            let j = self.below(i as u64 + 1) as usize;
+            items.swap(i, j);
+        }
+    }
+}
+
+// A rectangular grid stored row by row. Positions are (x, y) with (0, 0) at the top left,
+// and a cell's index is its place in that row-by-row order.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Grid<T> {
+    width: usize,
+    height: usize,
+    cells: Vec<T>,
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Neighbours {
+    Four,
+    Eight,
+}
+
+#[allow(dead_code)]
+impl<T> Grid<T> {
+    pub fn from_cells(width: usize, height: usize, cells: Vec<T>) -> Self {
+        assert_eq!(cells.len(), width * height, "Grid of {}x{} needs {} cells", width, height, width * height);
+        Grid { width, height, cells }
+    }
+
+    // Splits each line into whitespace-separated tokens, one cell per token.
+    pub fn parse_tokens(lines: impl Iterator<Item=String>, mut parse_cell: impl FnMut(&str) -> T) -> Self {
+        Grid::from_rows(lines.map(|line| line.split_whitespace().map(&mut parse_cell).collect()))
+    }
+
+    // One cell per character, for digit maps and the like.
+    pub fn parse_chars(lines: impl Iterator<Item=String>, mut parse_cell: impl FnMut(char) -> T) -> Self {
+        Grid::from_rows(lines.map(|line| line.chars().map(&mut parse_cell).collect()))
+    }
+
+    pub fn from_rows(rows: impl Iterator<Item=Vec<T>>) -> Self {
+        let mut cells = vec![];
+        let mut width = 0;
+        let mut height = 0;
+        for row in rows {
+            if height == 0 {
+                width = row.len();
+            } else if row.len() != width {
+                panic!("Grid row {} has {} cells, expected {}", height, row.len(), width);
+            }
+            cells.extend(row);
+            height += 1;
+        }
+        Grid { width, height, cells }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn len(&self) -> usize {
+        self.cells.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cells.is_empty()
+    }
+
+    pub fn is_square(&self) -> bool {
+        self.width == self.height
+    }
+
+    pub fn contains(&self, x: usize, y: usize) -> bool {
+        x < self.width && y < self.height
+    }
+
+    pub fn index_of(&self, x: usize, y: usize) -> usize {
+        y * self.width + x
+    }
+
+    pub fn position_of(&self, index: usize) -> (usize, usize) {
+        (index % self.width, index / self.width)
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> Option<&T> {
+        if self.contains(x, y) { Some(&self.cells[self.index_of(x, y)]) } else { None }
+    }
+
+    pub fn get_mut(&mut self, x: usize, y: usize) -> Option<&mut T> {
+        if self.contains(x, y) {
+            let index = self.index_of(x, y);
+            Some(&mut self.cells[index])
+        } else {
+            None
+        }
+    }
+
+    pub fn map<U>(&self, f: impl FnMut(&T) -> U) -> Grid<U> {
+        Grid { width: self.width, height: self.height, cells: self.cells.iter().map(f).collect() }
+    }
+
+    pub fn cells(&self) -> impl Iterator<Item=&T> {
+        self.cells.iter()
+    }
+
+    pub fn positions(&self) -> impl Iterator<Item=(usize, usize)> {
+        let width = self.width;
+        (0..self.cells.len()).map(move |index| (index % width, index / width))
+    }
+
+    pub fn rows(&self) -> impl Iterator<Item=&[T]> {
+        self.cells.chunks(self.width.max(1))
+    }
+
+    pub fn row(&self, y: usize) -> impl Iterator<Item=&T> {
+        self.row_indexes(y).map(|index| &self.cells[index])
+    }
+
+    pub fn column(&self, x: usize) -> impl Iterator<Item=&T> {
+        self.column_indexes(x).map(|index| &self.cells[index])
+    }
+
+    pub fn main_diagonal(&self) -> impl Iterator<Item=&T> {
+        self.main_diagonal_indexes().map(|index| &self.cells[index])
+    }
+
+    pub fn anti_diagonal(&self) -> impl Iterator<Item=&T> {
+        self.anti_diagonal_indexes().map(|index| &self.cells[index])
+    }
+
+    pub fn row_indexes(&self, y: usize) -> impl Iterator<Item=usize> {
+        assert!(y < self.height, "Row {} is outside a grid of height {}", y, self.height);
+        y * self.width..(y + 1) * self.width
+    }
+
+    pub fn column_indexes(&self, x: usize) -> impl Iterator<Item=usize> {
+        assert!(x < self.width, "Column {} is outside a grid of width {}", x, self.width);
+        let width = self.width;
+        (0..self.height).map(move |y| y * width + x)
+    }
+
+    // Top left to bottom right.
+    pub fn main_diagonal_indexes(&self) -> impl Iterator<Item=usize> {
+        assert!(self.is_square(), "Grid of {}x{} has no diagonal", self.width, self.height);
+        let width = self.width;
+        (0..width).map(move |i| i * width + i)
+    }
+
+    // Top right to bottom left.
+    pub fn anti_diagonal_indexes(&self) -> impl Iterator<Item=usize> {
+        assert!(self.is_square(), "Grid of {}x{} has no diagonal", self.width, self.height);
+        let width = self.width;
+        (0..width).map(move |i| i * width + (width - 1 - i))
+    }
+
+    // The positions next to (x, y) that are inside the grid, in row order.
+    pub fn neighbours(&self, x: usize, y: usize, neighbours: Neighbours) -> impl Iterator<Item=(usize, usize)> + '_ {
+        let offsets: &[(isize, isize)] = match neighbours {
+            Neighbours::Four => &[(0, -1), (-1, 0), (1, 0), (0, 1)],
+            Neighbours::Eight => &[(-1, -1), (0, -1), (1, -1), (-1, 0), (1, 0), (-1, 1), (0, 1), (1, 1)],
+        };
+        offsets.iter()
+            .filter_map(move |&(dx, dy)| Some((x.checked_add_signed(dx)?, y.checked_add_signed(dy)?)))
+            .filter(|&(x, y)| self.contains(x, y))
+    }
+}
+
+#[allow(dead_code)]
+impl<T: Clone> Grid<T> {
+    pub fn new(width: usize, height: usize, fill: T) -> Self {
+        Grid { width, height, cells: vec![fill; width * height] }
+    }
+
+    // Adds rows and columns of fill on each side. Existing cells move right by left and
+    // down by top.
+    pub fn grow(&mut self, left: usize, top: usize, right: usize, bottom: usize, fill: T) {
+        let width = left + self.width + right;
+        let height = top + self.height + bottom;
+        let mut cells = Vec::with_capacity(width * height);
+        cells.resize(top * width, fill.clone());
+        for row in self.cells.chunks(self.width.max(1)).take(self.height) {
+            cells.resize(cells.len() + left, fill.clone());
+            cells.extend_from_slice(row);
+            cells.resize(cells.len() + right, fill.clone());
+        }
+        cells.resize(width * height, fill);
+        *self = Grid { width, height, cells };
+    }
+}
+
+impl<T> Index<(usize, usize)> for Grid<T> {
+    type Output = T;
+
+    fn index(&self, (x, y): (usize, usize)) -> &T {
+        assert!(self.contains(x, y), "({}, {}) is outside a {}x{} grid", x, y, self.width, self.height);
+        &self.cells[y * self.width + x]
+    }
+}
+
+impl<T> IndexMut<(usize, usize)> for Grid<T> {
+    fn index_mut(&mut self, (x, y): (usize, usize)) -> &mut T {
+        assert!(self.contains(x, y), "({}, {}) is outside a {}x{} grid", x, y, self.width, self.height);
+        &mut self.cells[y * self.width + x]
+    }
+}
+
+// Line numbers and columns count from 1, and columns count characters.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub line_number: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl ParseError {
+    fn at(line: &str, line_number: usize, offset: usize, message: String) -> Self {
+        ParseError { line_number, column: column_at(line, offset), message }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}, column {}: {}", self.line_number, self.column, self.message)
+    }
+}
+
+fn parse_token<T: FromStr>(line: &str, line_number: usize, offset: usize, token: &str) -> Result<T, ParseError> {
+    if token.is_empty() {
+        return Err(ParseError::at(line, line_number, offset, "expected a value".to_string()));
+    }
+    token.parse().map_err(|_| ParseError::at(line, line_number, offset,
+                                              format!("can't parse {:?} as {}", token, short_type_name::<T>())))
+}
+
+// Drops the module path, so a board cell is "Cell" rather than "advent2021::day4::Cell".
+fn short_type_name<T>() -> &'static str {
+    type_name::<T>().rsplit("::").next().unwrap()
+}
+
+fn column_at(line: &str, offset: usize) -> usize {
+    line[..offset].chars().count() + 1
+}
+
+// Each token with its byte offset in the line.
+fn whitespace_tokens(line: &str) -> Vec<(usize, &str)> {
+    let mut tokens = vec![];
+    let mut token_start = None;
+    for (offset, c) in line.char_indices().chain([(line.len(), ' ')]) {
+        match (c.is_whitespace(), token_start) {
+            (false, None) => token_start = Some(offset),
+            (true, Some(start)) => {
+                tokens.push((start, &line[start..offset]));
+                token_start = None;
+            }
+            _ => {}
+        }
+    }
+    tokens
+}
+
+// Values split by the separator, ignoring whitespace around each one. A separator that is
+// only whitespace splits on any run of whitespace. A blank line is an empty list.
+pub fn parse_list<T: FromStr>(line: &str, line_number: usize, separator: &str) -> Result<Vec<T>, ParseError> {
+    parse_list_columns(line, line_number, separator).into_iter()
+        .map(|(_column, value)| value)
+        .collect()
+}
+
+// Like parse_list, but parses every value on its own and pairs it with its column, so one
+// bad value doesn't hide the rest.
+pub fn parse_list_columns<T: FromStr>(line: &str, line_number: usize, separator: &str) -> Vec<(usize, Result<T, ParseError>)> {
+    list_tokens(line, separator).into_iter()
+        .map(|(offset, token)| (column_at(line, offset), parse_token(line, line_number, offset, token)))
+        .collect()
+}
+
+fn list_tokens<'a>(line: &'a str, separator: &str) -> Vec<(usize, &'a str)> {
+    if separator.trim().is_empty() || line.trim().is_empty() {
+        return whitespace_tokens(line);
+    }
+    let mut start = 0;
+    line.match_indices(separator)
+        .map(|(offset, _separator)| offset)
+        .chain([line.len()])
+        .map(|end| {
+            let piece = &line[start..end];
+            let offset = start + (piece.len() - piece.trim_start().len());
+            start = end + separator.len();
+            (offset, piece.trim())
+        })
+        .collect()
+}
+
+// A run of non-blank lines.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Block {
+    pub first_line_number: usize,
+    pub lines: Vec<String>,
+}
+
+// Splits lines into blocks at blank lines, numbering them from first_line_number.
+pub fn read_blocks(lines: impl Iterator<Item=String>, first_line_number: usize) -> Vec<Block> {
+    let mut blocks: Vec<Block> = vec![];
+    let mut in_block = false;
+    for (line_index, line) in lines.enumerate() {
+        if line.trim().is_empty() {
+            in_block = false;
+        } else if in_block {
+            blocks.last_mut().unwrap().lines.push(line);
+        } else {
+            blocks.push(Block { first_line_number: first_line_number + line_index, lines: vec![line] });
+            in_block = true;
+        }
+    }
+    blocks
+}
+
+// The text matched by each {} in a pattern, to be parsed as whatever type it holds.
+#[derive(Debug)]
+pub struct Captures<'a> {
+    line: &'a str,
+    line_number: usize,
+    spans: Vec<(usize, usize)>,
+}
+
+#[allow(dead_code)]
+impl<'a> Captures<'a> {
+    pub fn len(&self) -> usize {
+        self.spans.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.spans.is_empty()
+    }
+
+    pub fn str(&self, capture_index: usize) -> &'a str {
+        let (start, end) = self.spans[capture_index];
+        &self.line[start..end]
+    }
+
+    pub fn parse<T: FromStr>(&self, capture_index: usize) -> Result<T, ParseError> {
+        let (start, _end) = self.spans[capture_index];
+        parse_token(self.line, self.line_number, start, self.str(capture_index))
+    }
+
+    // For values that parse but aren't allowed, such as an index out of range.
+    pub fn error(&self, capture_index: usize, message: String) -> ParseError {
+        let (start, _end) = self.spans[capture_index];
+        ParseError::at(self.line, self.line_number, start, message)
+    }
+
+    // A capture holding a list, as parse_list would read it, with columns in the whole line.
+    pub fn parse_list<T: FromStr>(&self, capture_index: usize, separator: &str) -> Result<Vec<T>, ParseError> {
+        let (start, _end) = self.spans[capture_index];
+        list_tokens(self.str(capture_index), separator).into_iter()
+            .map(|(offset, token)| parse_token(self.line, self.line_number, start + offset, token))
+            .collect()
+    }
+}
+
+// Matches a line against a pattern like "{},{} -> {},{}", where the text between captures
+// must appear exactly, except that text of only whitespace matches any run of whitespace. Each
+// capture runs up to the next occurrence of the text after it, and whitespace around a capture
+// isn't part of it.
+pub fn match_pattern<'a>(line: &'a str, line_number: usize, pattern: &str) -> Result<Captures<'a>, ParseError> {
+    let literals = pattern.split("{}").collect::<Vec<_>>();
+    let between_captures = literals[1..].split_last().map_or(&[][..], |(_last, between)| between);
+    assert!(between_captures.iter().all(|literal| !literal.is_empty()),
+            "Pattern {:?} has captures with nothing between them", pattern);
+    let expected = |offset: usize, literal: &str| ParseError::at(line, line_number, offset, format!("expected {:?}", literal));
+
+    if !line.starts_with(literals[0]) {
+        return Err(expected(0, literals[0]));
+    }
+    let mut offset = literals[0].len();
+    let mut spans = vec![];
+    for &literal in &literals[1..] {
+        offset = line.len() - line[offset..].trim_start().len();
+        let (end, literal_end) = if literal.is_empty() {
+            (line.len(), line.len())
+        } else if literal.trim().is_empty() {
+            // Text between captures that is only whitespace matches any run of whitespace.
+            let end = line[offset..].find(char::is_whitespace)
+                .map(|literal_offset| offset + literal_offset)
+                .ok_or_else(|| expected(line.len(), literal))?;
+            (end, line.len() - line[end..].trim_start().len())
+        } else {
+            let end = line[offset..].find(literal)
+                .map(|literal_offset| offset + literal_offset)
+                .ok_or_else(|| expected(line.len(), literal))?;
+            (end, end + literal.len())
+        };
+        if end == offset {
+            return Err(ParseError::at(line, line_number, offset, "expected a value".to_string()));
+        }
+        spans.push((offset, offset + line[offset..end].trim_end().len()));
+        offset = literal_end;
+    }
+    if offset != line.len() {
+        return Err(ParseError::at(line, line_number, offset, format!("unexpected {:?}", &line[offset..])));
+    }
+    Ok(Captures { line, line_number, spans })
+}
+
+// A table of whitespace-separated values with the same number in every row.
+pub fn parse_table<T: FromStr>(lines: impl Iterator<Item=String>, first_line_number: usize) -> Result<Grid<T>, ParseError> {
+    let mut rows = vec![];
+    for (line_index, line) in lines.enumerate() {
+        let line_number = first_line_number + line_index;
+        let tokens = whitespace_tokens(&line);
+        if let Some(first_row) = rows.first().map(|row: &Vec<T>| row.len()) {
+            if tokens.len() != first_row {
+                let offset = tokens.get(first_row).map_or(line.len(), |&(offset, _token)| offset);
+                return Err(ParseError::at(&line, line_number, offset,
+                                          format!("row has {} values, expected {}", tokens.len(), first_row)));
+            }
+        }
+        rows.push(tokens.into_iter()
+            .map(|(offset, token)| parse_token(&line, line_number, offset, token))
+            .collect::<Result<Vec<_>, _>>()?);
+    }
+    Ok(Grid::from_rows(rows.into_iter()))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::util::*;
+
+    #[test]
+    fn rng_is_reproducible() {
+        let mut rng1 = Rng::new(42);
+        let mut rng2 = Rng::new(42);
+        assert_eq!((0..5).map(|_| rng1.next_u64()).collect::<Vec<_>>(),
+                   (0..5).map(|_| rng2.next_u64()).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn rng_below_stays_in_range() {
+        let mut rng = Rng::new(7);
+        assert!((0..1000).all(|_| rng.below(6) < 6));
+    }
+
+    #[test]
+    fn shuffle_keeps_all_items() {
+        let mut items = (0..20).collect::<Vec<_>>();
+        Rng::new(1).shuffle(&mut items);
+        assert_ne!(items, (0..20).collect::<Vec<_>>());
+        items.sort();
+        assert_eq!(items, (0..20).collect::<Vec<_>>());
+    }
+
+    fn digit_grid() -> Grid<u32> {
+        Grid::parse_chars(to_string_iter(vec![
+            "123",
+            "456",
+            "789",
+        ]), |digit| digit.to_digit(10).unwrap())
+    }
+
+    #[test]
+    fn grid_indexing() {
+        let mut grid = digit_grid();
+        assert_eq!((grid.width(), grid.height()), (3, 3));
+        assert_eq!(grid[(2, 1)], 6);
+        assert_eq!(grid.get(3, 0), None);
+        grid[(0, 2)] = 0;
+        assert_eq!(grid.get(0, 2), Some(&0));
+        assert_eq!(grid.index_of(1, 2), 7);
+        assert_eq!(grid.position_of(7), (1, 2));
+    }
+
+    #[test]
+    fn grid_lines() {
+        let grid = digit_grid();
+        assert_eq!(grid.row(1).copied().collect::<Vec<_>>(), vec![4, 5, 6]);
+        assert_eq!(grid.column(2).copied().collect::<Vec<_>>(), vec![3, 6, 9]);
+        assert_eq!(grid.main_diagonal().copied().collect::<Vec<_>>(), vec![1, 5, 9]);
+        assert_eq!(grid.anti_diagonal().copied().collect::<Vec<_>>(), vec![3, 5, 7]);
+        assert_eq!(grid.rows().collect::<Vec<_>>(), vec![&[1, 2, 3][..], &[4, 5, 6], &[7, 8, 9]]);
+    }
+
+    #[test]
+    fn grid_neighbours() {
+        let grid = digit_grid();
+        assert_eq!(grid.neighbours(0, 0, Neighbours::Four).collect::<Vec<_>>(), vec![(1, 0), (0, 1)]);
+        assert_eq!(grid.neighbours(2, 1, Neighbours::Eight).collect::<Vec<_>>(),
+                   vec![(1, 0), (2, 0), (1, 1), (1, 2), (2, 2)]);
+        assert_eq!(grid.neighbours(1, 1, Neighbours::Eight).count(), 8);
+    }
+
+    #[test]
+    fn grid_grows_on_every_side() {
+        let mut grid = Grid::from_cells(2, 1, vec![1, 2]);
+        grid.grow(1, 2, 0, 1, 0);
+        assert_eq!((grid.width(), grid.height()), (3, 4));
+        assert_eq!(grid.rows().collect::<Vec<_>>(), vec![&[0, 0, 0][..], &[0, 0, 0], &[0, 1, 2], &[0, 0, 0]]);
+    }
+
+    #[test]
+    fn grid_parses_tokens() {
+        let grid = Grid::parse_tokens(to_string_iter(vec![
+            "10 -2",
+            " 7  40",
+        ]), |token| token.parse::<i32>().unwrap());
+        assert_eq!(grid, Grid::from_cells(2, 2, vec![10, -2, 7, 40]));
+    }
+
+    #[test]
+    #[should_panic(expected = "Grid row 1 has 2 cells, expected 3")]
+    fn grid_rejects_ragged_rows() {
+        Grid::parse_chars(to_string_iter(vec!["abc", "de"]), |c| c);
+    }
+
+    #[test]
+    #[should_panic(expected = "Grid of 2x3 has no diagonal")]
+    fn grid_diagonal_needs_square() {
+        Grid::new(2, 3, 0).main_diagonal_indexes().count();
+    }
+
+    #[test]
+    fn parse_list_with_any_separator() {
+        assert_eq!(parse_list::<u32>("7,4,9,5,11", 1, ","), Ok(vec![7, 4, 9, 5, 11]));
+        assert_eq!(parse_list::<i64>(" 3 ; -4;5 ", 1, ";"), Ok(vec![3, -4, 5]));
+        assert_eq!(parse_list::<u8>("  1   2 3", 1, " "), Ok(vec![1, 2, 3]));
+        assert_eq!(parse_list::<u8>("", 1, ","), Ok(vec![]));
+    }
+
+    #[test]
+    fn parse_list_reports_bad_value() {
+        let error = parse_list::<u32>("7,4, x9,5", 3, ",").unwrap_err();
+        assert_eq!(error, ParseError { line_number: 3, column: 6, message: "can't parse \"x9\" as u32".to_string() });
+        assert_eq!(error.to_string(), "line 3, column 6: can't parse \"x9\" as u32");
+        assert_eq!(parse_list::<u32>("7,,5", 1, ",").unwrap_err().column, 3);
+    }
+
+    #[test]
+    fn parse_list_columns_keeps_going_after_bad_value() {
+        let values = parse_list_columns::<u32>("7, x,5", 2, ",");
+        assert_eq!(values.iter().map(|(column, _value)| *column).collect::<Vec<_>>(), vec![1, 4, 6]);
+        assert_eq!(values[0].1, Ok(7));
+        assert_eq!(values[1].1.as_ref().unwrap_err().to_string(), "line 2, column 4: can't parse \"x\" as u32");
+        assert_eq!(values[2].1, Ok(5));
+    }
+
+    #[test]
+    fn read_blocks_between_blank_lines() {
+        let blocks = read_blocks(to_string_iter(vec!["a", "b", "", "", "c", "  ", "d"]), 2);
+        assert_eq!(blocks, vec![
+            Block { first_line_number: 2, lines: vec!["a".to_string(), "b".to_string()] },
+            Block { first_line_number: 6, lines: vec!["c".to_string()] },
+            Block { first_line_number: 8, lines: vec!["d".to_string()] },
+        ]);
+    }
+
+    #[test]
+    fn match_pattern_with_typed_captures() {
+        let captures = match_pattern("957,596 -> 35,-182", 1, "{},{} -> {},{}").unwrap();
+        assert_eq!(captures.len(), 4);
+        assert_eq!(captures.parse::<u32>(0), Ok(957));
+        assert_eq!(captures.parse::<i64>(3), Ok(-182));
+        let captures = match_pattern("forward 5", 1, "{} {}").unwrap();
+        assert_eq!((captures.str(0), captures.parse::<u32>(1)), ("forward", Ok(5)));
+    }
+
+    #[test]
+    fn match_pattern_reports_where_line_differs() {
+        let error = match_pattern("957,596 => 35,182", 4, "{},{} -> {},{}").unwrap_err();
+        assert_eq!(error.to_string(), "line 4, column 18: expected \" -> \"");
+        let error = match_pattern("1,2 -> 3,4!", 4, "{},{} -> {},{}!!").unwrap_err();
+        assert_eq!(error.to_string(), "line 4, column 12: expected \"!!\"");
+        let error = match_pattern("pos=1,2 extra", 1, "pos={},{} extra?").unwrap_err();
+        assert_eq!(error.to_string(), "line 1, column 14: expected \" extra?\"");
+        let error = match_pattern("x=,2", 1, "x={},{}").unwrap_err();
+        assert_eq!(error.to_string(), "line 1, column 3: expected a value");
+        let captures = match_pattern("1,2 -> 3,x", 9, "{},{} -> {},{}").unwrap();
+        assert_eq!(captures.parse::<u32>(3).unwrap_err().to_string(), "line 9, column 10: can't parse \"x\" as u32");
+    }
+
+    #[test]
+    fn match_pattern_ignores_whitespace_around_captures() {
+        let captures = match_pattern(" forward  5 \r", 1, "{} {}").unwrap();
+        assert_eq!(captures.str(0), "forward");
+        assert_eq!(captures.parse::<u32>(1), Ok(5));
+        let captures = match_pattern("1 , 2", 1, "{},{}").unwrap();
+        assert_eq!((captures.str(0), captures.str(1)), ("1", "2"));
+        assert_eq!(match_pattern("up   ", 4, "{} {}").unwrap_err().to_string(), "line 4, column 6: expected a value");
+    }
+
+    #[test]
+    fn match_pattern_with_list_capture() {
+        let captures = match_pattern("line 0,1, x score 7", 4, "line {} score {}").unwrap();
+        assert_eq!(captures.parse_list::<u32>(0, ",").unwrap_err().to_string(), "line 4, column 11: can't parse \"x\" as u32");
+        let captures = match_pattern("line 0, 1,2 score 7", 4, "line {} score {}").unwrap();
+        assert_eq!(captures.parse_list::<u32>(0, ","), Ok(vec![0, 1, 2]));
+    }
+
+    #[test]
+    fn match_pattern_without_captures() {
+        assert_eq!(match_pattern("done", 1, "done").unwrap().len(), 0);
+        assert_eq!(match_pattern("done!", 5, "done").unwrap_err().to_string(), "line 5, column 5: unexpected \"!\"");
+        assert_eq!(match_pattern("don", 5, "done").unwrap_err().to_string(), "line 5, column 1: expected \"done\"");
+    }
+
+    #[test]
+    fn match_pattern_rejects_trailing_text() {
+        let captures = match_pattern("up 3 now", 1, "up {} now").unwrap();
+        assert_eq!(captures.str(0), "3");
+        let error = match_pattern("x=3;", 2, "x={};y").unwrap_err();
+        assert_eq!(error.to_string(), "line 2, column 5: expected \";y\"");
+        let error = match_pattern("[1] and more", 2, "[{}]").unwrap_err();
+        assert_eq!(error.to_string(), "line 2, column 4: unexpected \" and more\"");
+    }
+
+    #[test]
+    fn parse_aligned_table() {
+        let table = parse_table::<u32>(to_string_iter(vec![
+            "22 13 17",
+            " 8  2 23",
+        ]), 3).unwrap();
+        assert_eq!(table, Grid::from_cells(3, 2, vec![22, 13, 17, 8, 2, 23]));
+    }
+
+    #[test]
+    fn parse_table_reports_ragged_row_and_bad_value() {
+        let error = parse_table::<u32>(to_string_iter(vec!["1 2 3", "4 5 6 7"]), 3).unwrap_err();
+        assert_eq!(error.to_string(), "line 4, column 7: row has 4 values, expected 3");
+        let error = parse_table::<u32>(to_string_iter(vec!["1 2 3", "4 5"]), 3).unwrap_err();
+        assert_eq!(error.to_string(), "line 4, column 4: row has 2 values, expected 3");
+        let error = parse_table::<u32>(to_string_iter(vec!["1 2 3", "4 -5 6"]), 3).unwrap_err();
+        assert_eq!(error.to_string(), "line 4, column 3: can't parse \"-5\" as u32");
+    }
 }